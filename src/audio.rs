@@ -0,0 +1,152 @@
+/*
+XO-CHIP replaces the fixed buzzer with a 16 byte pattern buffer that is played back one bit at a time while the sound
+timer is non-zero. F002 loads the pattern from memory at the index register and FX3A sets the pitch register, which picks
+the playback rate: 4000 * 2^((pitch - 64) / 48) bits per second, so the default pitch of 64 plays 4000 bits a second.
+
+MEGA-CHIP can also play digitised sound: 8-bit unsigned samples at their own sample rate, once or looping, regardless of
+the sound timer. While they play they replace the pattern.
+
+The generator doesn't know about audio devices, it fills whatever sample buffer it is given which keeps it testable. The
+window frontend doesn't open an output device yet, that needs an audio backend crate this project doesn't depend on.
+*/
+
+pub const PATTERN_BYTES: usize = 16;
+const PATTERN_BITS: f64 = (PATTERN_BYTES * 8) as f64;
+const VOLUME: f32 = 0.25;
+
+pub struct Audio {
+    pattern: [u8; PATTERN_BYTES],
+    pitch: u8,
+    position: f64, // bit of the pattern that is playing, the fraction carries over between buffers
+    digitised: Option<Digitised>,
+}
+
+struct Digitised {
+    samples: Vec<u8>,
+    sample_rate: u32,
+    looping: bool,
+    position: f64,
+}
+
+impl Audio {
+    pub fn new() -> Self {
+        Self {
+            pattern: [0xF0; PATTERN_BYTES], // 500 Hz square wave for programs that never load a pattern
+            pitch: 64,
+            position: 0.0,
+            digitised: None,
+        }
+    }
+
+    /// Play 8-bit unsigned samples, replacing whatever digitised sound was playing
+    pub fn play_samples(&mut self, samples: Vec<u8>, sample_rate: u32, looping: bool) {
+        self.digitised = Some(Digitised {
+            samples,
+            sample_rate,
+            looping,
+            position: 0.0,
+        });
+    }
+
+    pub fn stop_samples(&mut self) {
+        self.digitised = None;
+    }
+
+    pub fn load_pattern(&mut self, pattern: &[u8]) {
+        let len = pattern.len().min(PATTERN_BYTES);
+        self.pattern[..len].copy_from_slice(&pattern[..len]);
+    }
+
+    pub fn set_pitch(&mut self, pitch: u8) {
+        self.pitch = pitch;
+    }
+
+    /// Pattern bits played per second
+    pub fn playback_rate(&self) -> f64 {
+        4000.0 * 2f64.powf((self.pitch as f64 - 64.0) / 48.0)
+    }
+
+    /// Fill `samples` with audio at `sample_rate`, silence if the sound timer isn't running.
+    pub fn render(&mut self, samples: &mut [f32], sample_rate: u32, playing: bool) {
+        if let Some(digitised) = &mut self.digitised {
+            let step = digitised.sample_rate as f64 / sample_rate as f64;
+            for sample in samples.iter_mut() {
+                let index = digitised.position as usize;
+                if index >= digitised.samples.len() {
+                    *sample = 0.0;
+                    continue;
+                }
+                *sample = (digitised.samples[index] as f32 - 128.0) / 128.0 * VOLUME;
+
+                digitised.position += step;
+                if digitised.looping {
+                    digitised.position %= digitised.samples.len() as f64;
+                }
+            }
+            if digitised.position as usize >= digitised.samples.len() {
+                self.digitised = None;
+            }
+            return;
+        }
+
+        if !playing {
+            samples.fill(0.0);
+            return;
+        }
+
+        let step = self.playback_rate() / sample_rate as f64;
+        for sample in samples.iter_mut() {
+            let bit = self.position as usize;
+            let on = self.pattern[bit / 8] & (0x80 >> (bit % 8)) != 0;
+            *sample = if on { VOLUME } else { -VOLUME };
+
+            self.position = (self.position + step) % PATTERN_BITS;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pitch_sets_the_playback_rate() {
+        let mut audio = Audio::new();
+        assert_eq!(audio.playback_rate(), 4000.0);
+        audio.set_pitch(112);
+        assert_eq!(audio.playback_rate(), 8000.0);
+    }
+
+    #[test]
+    fn renders_the_pattern_bit_by_bit() {
+        let mut audio = Audio::new();
+        let mut pattern = [0; PATTERN_BYTES];
+        pattern[0] = 0b1010_0000;
+        audio.load_pattern(&pattern);
+
+        // at 4000 Hz every sample is one bit of the pattern
+        let mut samples = [0.0; 4];
+        audio.render(&mut samples, 4000, true);
+        assert_eq!(samples, [VOLUME, -VOLUME, VOLUME, -VOLUME]);
+
+        audio.render(&mut samples, 4000, false);
+        assert_eq!(samples, [0.0; 4]);
+    }
+
+    #[test]
+    fn digitised_samples_play_once_or_loop() {
+        let mut audio = Audio::new();
+        audio.play_samples(vec![0xFF, 0x80, 0x00], 8000, false);
+
+        // at half the sample rate every sample is played twice
+        let mut samples = [1.0; 8];
+        audio.render(&mut samples, 16000, false);
+        assert_eq!(samples[..6], [VOLUME * 127.0 / 128.0, VOLUME * 127.0 / 128.0, 0.0, 0.0, -VOLUME, -VOLUME]);
+        assert_eq!(samples[6..], [0.0, 0.0]);
+
+        audio.play_samples(vec![0xFF, 0x00], 8000, true);
+        let mut samples = [0.0; 4];
+        audio.render(&mut samples, 8000, false);
+        assert_eq!(samples, [VOLUME * 127.0 / 128.0, -VOLUME, VOLUME * 127.0 / 128.0, -VOLUME]);
+    }
+}
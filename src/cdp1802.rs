@@ -0,0 +1,307 @@
+/*
+The COSMAC VIP ran its CHIP8 interpreter on an RCA CDP1802, and 0NNN jumped straight into 1802 machine code at NNN. Some
+of the original VIP programs ("hybrid" ROMs) rely on this, so this is a small 1802 core that runs those subroutines
+against the emulator's memory.
+
+The 1802 has sixteen 16-bit scratchpad registers R0-RF. P picks which one is the program counter and X which one is the
+data pointer, D is the 8-bit accumulator and DF its carry flag. Opcodes are one byte, split into I (high nibble, the
+operation) and N (low nibble, usually a register), immediate operands and branch targets follow in memory.
+
+The VIP interpreter keeps its state in fixed places that machine code subroutines poke at directly:
+
+    R2        stack pointer (X = 2), growing down from 0xECF
+    R3        program counter of the subroutine (P = 3)
+    R4        program counter of the interpreter, a subroutine returns with SEP R4 (D4)
+    R5        CHIP8 program counter
+    R6 / R7   point at VX / VY of the current instruction
+    R8        R8.1 is the delay timer, R8.0 the sound timer
+    RA        index register
+    RB        RB.1 is the page of the display buffer
+
+    0xEF0 - 0xEFF   V0 - VF
+    0xF00 - 0xFFF   64x32 display buffer, one bit per pixel
+*/
+
+pub const VIP_STACK: u16 = 0xECF;
+pub const VIP_REGISTERS: u16 = 0xEF0;
+pub const VIP_DISPLAY: u16 = 0xF00;
+
+pub struct Cdp1802 {
+    pub r: [u16; 16], // scratchpad registers
+    pub p: u8,        // which register is the program counter
+    pub x: u8,        // which register is the data pointer
+    pub d: u8,        // accumulator
+    pub df: bool,     // carry / not borrow
+    pub t: u8,        // X and P saved by MARK and interrupts
+    pub ie: bool,     // interrupt enable
+    pub q: bool,      // output flip flop (the VIP's buzzer)
+    pub ef: [bool; 4],// external flags EF1 - EF4
+    pub idle: bool,   // IDL ran, waiting for an interrupt
+}
+
+impl Cdp1802 {
+    pub fn new() -> Self {
+        Self {
+            r: [0; 16],
+            p: 0,
+            x: 0,
+            d: 0,
+            df: false,
+            t: 0,
+            ie: true,
+            q: false,
+            ef: [false; 4],
+            idle: false,
+        }
+    }
+
+    fn read(&self, memory: &[u8], address: u16) -> u8 {
+        memory[address as usize % memory.len()]
+    }
+
+    fn write(&self, memory: &mut [u8], address: u16, value: u8) {
+        let len = memory.len();
+        memory[address as usize % len] = value;
+    }
+
+    // next byte at the program counter
+    fn immediate(&mut self, memory: &[u8]) -> u8 {
+        let value = self.read(memory, self.r[self.p as usize]);
+        self.r[self.p as usize] = self.r[self.p as usize].wrapping_add(1);
+        value
+    }
+
+    fn rx(&self, memory: &[u8]) -> u8 {
+        self.read(memory, self.r[self.x as usize])
+    }
+
+    fn add(&mut self, a: u8, b: u8, carry: bool) {
+        let sum = a as u16 + b as u16 + carry as u16;
+        self.d = sum as u8;
+        self.df = sum > 0xFF;
+    }
+
+    // a - b, DF is set when there was no borrow
+    fn subtract(&mut self, a: u8, b: u8, borrow: bool) {
+        let difference = a as i16 - b as i16 - borrow as i16;
+        self.d = difference as u8;
+        self.df = difference >= 0;
+    }
+
+    fn short_branch(&mut self, memory: &[u8], taken: bool) {
+        let p = self.p as usize;
+        if taken {
+            let target = self.read(memory, self.r[p]);
+            self.r[p] = (self.r[p] & 0xFF00) | target as u16;
+        } else {
+            self.r[p] = self.r[p].wrapping_add(1);
+        }
+    }
+
+    fn long_branch(&mut self, memory: &[u8], taken: bool) {
+        let p = self.p as usize;
+        if taken {
+            let high = self.read(memory, self.r[p]);
+            let low = self.read(memory, self.r[p].wrapping_add(1));
+            self.r[p] = u16::from_be_bytes([high, low]);
+        } else {
+            self.r[p] = self.r[p].wrapping_add(2);
+        }
+    }
+
+    fn long_skip(&mut self, taken: bool) {
+        if taken {
+            let p = self.p as usize;
+            self.r[p] = self.r[p].wrapping_add(2);
+        }
+    }
+
+    /// Fetch and execute one instruction
+    pub fn step(&mut self, memory: &mut [u8]) {
+        if self.idle {
+            return;
+        }
+
+        let opcode = self.immediate(memory);
+        let n = (opcode & 0xF) as usize;
+        let x = self.x as usize;
+
+        match opcode >> 4 {
+            0x0 if n == 0 => self.idle = true, // IDL
+            0x0 => self.d = self.read(memory, self.r[n]), // LDN
+            0x1 => self.r[n] = self.r[n].wrapping_add(1), // INC
+            0x2 => self.r[n] = self.r[n].wrapping_sub(1), // DEC
+            0x3 => { // short branches, the upper half negates the condition
+                let condition = match n & 0x7 {
+                    0 => true,
+                    1 => self.q,
+                    2 => self.d == 0,
+                    3 => self.df,
+                    flag => self.ef[flag - 4],
+                };
+                if n == 0x8 { // SKP
+                    self.short_branch(memory, false);
+                } else {
+                    self.short_branch(memory, condition != (n >= 0x8));
+                }
+            },
+            0x4 => { // LDA
+                self.d = self.read(memory, self.r[n]);
+                self.r[n] = self.r[n].wrapping_add(1);
+            },
+            0x5 => self.write(memory, self.r[n], self.d), // STR
+            0x6 => match n {
+                0x0 => self.r[x] = self.r[x].wrapping_add(1), // IRX
+                0x1..=0x7 => self.r[x] = self.r[x].wrapping_add(1), // OUT, nothing is listening
+                0x8 => {}, // not an 1802 instruction
+                _ => { // INP, the bus reads as 0
+                    self.d = 0;
+                    self.write(memory, self.r[x], 0);
+                },
+            },
+            0x7 => match n {
+                0x0 | 0x1 => { // RET / DIS
+                    let xp = self.rx(memory);
+                    self.r[x] = self.r[x].wrapping_add(1);
+                    self.x = xp >> 4;
+                    self.p = xp & 0xF;
+                    self.ie = n == 0x0;
+                },
+                0x2 => { // LDXA
+                    self.d = self.rx(memory);
+                    self.r[x] = self.r[x].wrapping_add(1);
+                },
+                0x3 => { // STXD
+                    self.write(memory, self.r[x], self.d);
+                    self.r[x] = self.r[x].wrapping_sub(1);
+                },
+                0x4 => self.add(self.rx(memory), self.d, self.df), // ADC
+                0x5 => self.subtract(self.rx(memory), self.d, !self.df), // SDB
+                0x6 => { // SHRC
+                    let carry = self.d & 1 != 0;
+                    self.d = (self.d >> 1) | ((self.df as u8) << 7);
+                    self.df = carry;
+                },
+                0x7 => self.subtract(self.d, self.rx(memory), !self.df), // SMB
+                0x8 => self.write(memory, self.r[x], self.t), // SAV
+                0x9 => { // MARK
+                    self.t = (self.x << 4) | self.p;
+                    self.write(memory, self.r[2], self.t);
+                    self.x = self.p;
+                    self.r[2] = self.r[2].wrapping_sub(1);
+                },
+                0xA => self.q = false, // REQ
+                0xB => self.q = true, // SEQ
+                0xC => { // ADCI
+                    let value = self.immediate(memory);
+                    self.add(value, self.d, self.df);
+                },
+                0xD => { // SDBI
+                    let value = self.immediate(memory);
+                    self.subtract(value, self.d, !self.df);
+                },
+                0xE => { // SHLC
+                    let carry = self.d & 0x80 != 0;
+                    self.d = (self.d << 1) | self.df as u8;
+                    self.df = carry;
+                },
+                _ => { // SMBI
+                    let value = self.immediate(memory);
+                    self.subtract(self.d, value, !self.df);
+                },
+            },
+            0x8 => self.d = self.r[n] as u8, // GLO
+            0x9 => self.d = (self.r[n] >> 8) as u8, // GHI
+            0xA => self.r[n] = (self.r[n] & 0xFF00) | self.d as u16, // PLO
+            0xB => self.r[n] = (self.r[n] & 0x00FF) | ((self.d as u16) << 8), // PHI
+            0xC => match n {
+                0x0 => self.long_branch(memory, true), // LBR
+                0x1 => self.long_branch(memory, self.q), // LBQ
+                0x2 => self.long_branch(memory, self.d == 0), // LBZ
+                0x3 => self.long_branch(memory, self.df), // LBDF
+                0x4 => {}, // NOP
+                0x5 => self.long_skip(!self.q), // LSNQ
+                0x6 => self.long_skip(self.d != 0), // LSNZ
+                0x7 => self.long_skip(!self.df), // LSNF
+                0x8 => self.long_skip(true), // LSKP
+                0x9 => self.long_branch(memory, !self.q), // LBNQ
+                0xA => self.long_branch(memory, self.d != 0), // LBNZ
+                0xB => self.long_branch(memory, !self.df), // LBNF
+                0xC => self.long_skip(self.ie), // LSIE
+                0xD => self.long_skip(self.q), // LSQ
+                0xE => self.long_skip(self.d == 0), // LSZ
+                _ => self.long_skip(self.df), // LSDF
+            },
+            0xD => self.p = n as u8, // SEP
+            0xE => self.x = n as u8, // SEX
+            _ => match n {
+                0x0 => self.d = self.rx(memory), // LDX
+                0x1 => self.d |= self.rx(memory), // OR
+                0x2 => self.d &= self.rx(memory), // AND
+                0x3 => self.d ^= self.rx(memory), // XOR
+                0x4 => self.add(self.rx(memory), self.d, false), // ADD
+                0x5 => self.subtract(self.rx(memory), self.d, false), // SD
+                0x6 => { // SHR
+                    self.df = self.d & 1 != 0;
+                    self.d >>= 1;
+                },
+                0x7 => self.subtract(self.d, self.rx(memory), false), // SM
+                0x8 => self.d = self.immediate(memory), // LDI
+                0x9 => self.d |= self.immediate(memory), // ORI
+                0xA => self.d &= self.immediate(memory), // ANI
+                0xB => self.d ^= self.immediate(memory), // XRI
+                0xC => { // ADI
+                    let value = self.immediate(memory);
+                    self.add(value, self.d, false);
+                },
+                0xD => { // SDI
+                    let value = self.immediate(memory);
+                    self.subtract(value, self.d, false);
+                },
+                0xE => { // SHL
+                    self.df = self.d & 0x80 != 0;
+                    self.d <<= 1;
+                },
+                _ => { // SMI
+                    let value = self.immediate(memory);
+                    self.subtract(self.d, value, false);
+                },
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(program: &[u8], steps: usize) -> (Cdp1802, Vec<u8>) {
+        let mut memory = vec![0; 0x1000];
+        memory[..program.len()].copy_from_slice(program);
+        let mut cpu = Cdp1802::new();
+        for _ in 0..steps {
+            cpu.step(&mut memory);
+        }
+        (cpu, memory)
+    }
+
+    #[test]
+    fn arithmetic_sets_df() {
+        // LDI 0xF0, ADI 0x20, -> D = 0x10 with carry, SMI 0x20 -> D = 0xF0 with borrow
+        let (cpu, _) = run(&[0xF8, 0xF0, 0xFC, 0x20], 2);
+        assert_eq!((cpu.d, cpu.df), (0x10, true));
+
+        let (cpu, _) = run(&[0xF8, 0xF0, 0xFC, 0x20, 0xFF, 0x20], 3);
+        assert_eq!((cpu.d, cpu.df), (0xF0, false));
+    }
+
+    #[test]
+    fn store_through_register_and_branch() {
+        // 0x00: LDI 0x0F, PHI R1, LDI 0x00, PLO R1, LDI 0x42, STR R1, BNZ 0x0C, IDL, 0x0C: SEP R4
+        let program = [0xF8, 0x0F, 0xB1, 0xF8, 0x00, 0xA1, 0xF8, 0x42, 0x51, 0x3A, 0x0C, 0x00, 0xD4];
+        let (cpu, memory) = run(&program, 8);
+        assert_eq!(memory[0xF00], 0x42);
+        assert_eq!(cpu.p, 4);
+        assert!(!cpu.idle);
+    }
+}
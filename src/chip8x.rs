@@ -0,0 +1,127 @@
+/*
+CHIP-8X was RCA's interpreter for the VIP with the VP-590 colour board and the VP-595 sound board, and a second hex keypad.
+The colour board keeps a background colour for the whole screen and a foreground colour per zone: BXY0 colours 8x4 pixel
+zones, BXYN colours 8x1 strips N rows high. Pixels that are on take the foreground colour of their zone, pixels that are
+off take the background colour. The sound board is driven through the VIP's I/O port, which lives in `CHIP8` since
+CHIP-8E uses it too.
+*/
+
+use crate::frame_buffer::{LORES_HEIGHT, LORES_WIDTH};
+use crate::keypad::Keypad;
+
+pub const ZONE_COLUMNS: usize = LORES_WIDTH / 8; // 8 pixels wide
+pub const ZONE_ROWS: usize = LORES_HEIGHT;       // 1 pixel high, BXY0 zones are 4 of these
+
+// black, red, blue, violet, green, yellow, aqua, white
+pub const FOREGROUND_COLOURS: [[u8; 4]; 8] = [
+    [0x00, 0x00, 0x00, 0xff],
+    [0xff, 0x00, 0x00, 0xff],
+    [0x00, 0x00, 0xff, 0xff],
+    [0xff, 0x00, 0xff, 0xff],
+    [0x00, 0xff, 0x00, 0xff],
+    [0xff, 0xff, 0x00, 0xff],
+    [0x00, 0xff, 0xff, 0xff],
+    [0xff, 0xff, 0xff, 0xff],
+];
+
+// the order 02A0 cycles through: dark blue, black, green, red
+pub const BACKGROUND_COLOURS: [[u8; 4]; 4] = [
+    [0x00, 0x00, 0x80, 0xff],
+    [0x00, 0x00, 0x00, 0xff],
+    [0x00, 0x80, 0x00, 0xff],
+    [0x80, 0x00, 0x00, 0xff],
+];
+
+pub struct Chip8X {
+    background: usize,
+    zones: [[u8; ZONE_COLUMNS]; ZONE_ROWS], // foreground colour of each 8x1 strip
+    pub second_keypad: Keypad,
+}
+
+impl Chip8X {
+    pub fn new() -> Self {
+        Self {
+            background: 0,
+            zones: [[1; ZONE_COLUMNS]; ZONE_ROWS], // the colour board starts out red
+            second_keypad: Keypad::new(),
+        }
+    }
+
+    /// 02A0
+    pub fn cycle_background(&mut self) {
+        self.background = (self.background + 1) % BACKGROUND_COLOURS.len();
+    }
+
+    /// BXY0, `horizontal` and `vertical` hold the zone in the low nibble and the extent minus one in the high nibble
+    pub fn colour_zones(&mut self, horizontal: u8, vertical: u8, colour: u8) {
+        let (x, width) = ((horizontal & 0xF) as usize, (horizontal >> 4) as usize + 1);
+        let (y, height) = ((vertical & 0xF) as usize, (vertical >> 4) as usize + 1);
+
+        for zone_y in y..y + height {
+            for zone_x in x..x + width {
+                for row in zone_y * 4..zone_y * 4 + 4 {
+                    self.colour_strip(zone_x, row, colour);
+                }
+            }
+        }
+    }
+
+    /// BXYN, colours the 8 pixel wide strip containing column `x` for `rows` rows starting at `y`
+    pub fn colour_rows(&mut self, x: u8, y: u8, rows: u8, colour: u8) {
+        let column = (x as usize % LORES_WIDTH) / 8;
+        for row in y as usize..y as usize + rows as usize {
+            self.colour_strip(column, row % ZONE_ROWS, colour);
+        }
+    }
+
+    fn colour_strip(&mut self, column: usize, row: usize, colour: u8) {
+        if column < ZONE_COLUMNS && row < ZONE_ROWS {
+            self.zones[row][column] = colour & 0x7;
+        }
+    }
+
+    /// Colour of a lores pixel
+    pub fn rgba(&self, x: usize, y: usize, on: bool) -> [u8; 4] {
+        if on {
+            FOREGROUND_COLOURS[self.zones[y % ZONE_ROWS][(x / 8) % ZONE_COLUMNS] as usize]
+        } else {
+            BACKGROUND_COLOURS[self.background]
+        }
+    }
+}
+
+/// 5XY1 adds the nibbles of VX and VY separately, each one wrapping at 8
+pub fn add_nibbles(vx: u8, vy: u8) -> u8 {
+    ((vx & 0x77) + (vy & 0x77)) & 0x77
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nibble_addition_wraps_per_nibble() {
+        assert_eq!(add_nibbles(0x35, 0x14), 0x41);
+        assert_eq!(add_nibbles(0x77, 0x11), 0x00);
+    }
+
+    #[test]
+    fn zones_and_strips_take_colours() {
+        let mut board = Chip8X::new();
+        assert_eq!(board.rgba(0, 0, true), FOREGROUND_COLOURS[1]);
+
+        // zone (1, 0), two zones wide and one high
+        board.colour_zones(0x11, 0x00, 4);
+        assert_eq!(board.rgba(8, 3, true), FOREGROUND_COLOURS[4]);
+        assert_eq!(board.rgba(23, 0, true), FOREGROUND_COLOURS[4]);
+        assert_eq!(board.rgba(24, 0, true), FOREGROUND_COLOURS[1]);
+        assert_eq!(board.rgba(8, 4, true), FOREGROUND_COLOURS[1]);
+
+        board.colour_rows(8, 4, 1, 7);
+        assert_eq!(board.rgba(15, 4, true), FOREGROUND_COLOURS[7]);
+        assert_eq!(board.rgba(15, 5, true), FOREGROUND_COLOURS[1]);
+
+        board.cycle_background();
+        assert_eq!(board.rgba(0, 0, false), BACKGROUND_COLOURS[1]);
+    }
+}
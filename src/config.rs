@@ -0,0 +1,20 @@
+/*
+Where chip8 keeps its files between runs: the keymap and the RPL flags saved for each ROM. Both live in a `chip8`
+directory inside the platform's config directory.
+*/
+
+use std::{env, path::PathBuf};
+
+/// The platform's per-user config directory, chip8 keeps its files in a `chip8` directory inside it
+pub fn config_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    if cfg!(windows) {
+        return env::var_os("APPDATA").map(PathBuf::from);
+    }
+    if cfg!(target_os = "macos") {
+        return env::var_os("HOME").map(|home| PathBuf::from(home).join("Library").join("Application Support"));
+    }
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".config"))
+}
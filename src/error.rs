@@ -0,0 +1,52 @@
+/*
+Things a ROM can do wrong. Loading a ROM that doesn't fit is reported straight away, faults while running come back from
+`CHIP8::step` with the instruction that caused them left unexecuted, and the fault policy decides what happens next.
+*/
+
+use crate::name::{normalise, UnknownName};
+use std::{fmt, str::FromStr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip8Error {
+    RomTooLarge { size: usize, max: usize },
+    StackOverflow { pc: u16 },
+    StackUnderflow { pc: u16 },        // 00EE without a matching 2NNN
+    MemoryOutOfBounds { addr: u32, pc: u16 },
+    UnknownOpcode { opcode: u16, pc: u16 },
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Chip8Error::RomTooLarge { size, max } => write!(f, "ROM is {size} bytes, only {max} fit in memory"),
+            Chip8Error::StackOverflow { pc } => write!(f, "stack overflow at {pc:03X}"),
+            Chip8Error::StackUnderflow { pc } => write!(f, "return with an empty stack at {pc:03X}"),
+            Chip8Error::MemoryOutOfBounds { addr, pc } => write!(f, "memory access out of bounds at {addr:04X} by {pc:03X}"),
+            Chip8Error::UnknownOpcode { opcode, pc } => write!(f, "unknown opcode {opcode:04X} at {pc:03X}"),
+        }
+    }
+}
+
+impl std::error::Error for Chip8Error {}
+
+/// What the emulator does when the program faults
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FaultPolicy {
+    Halt,   // stop the program for good
+    #[default]
+    Ignore, // skip the faulting instruction and carry on, like unknown opcodes always were
+    Pause,  // pause on the faulting instruction so its state can be inspected, resuming retries it
+}
+
+impl FromStr for FaultPolicy {
+    type Err = UnknownName;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match normalise(s).as_str() {
+            "halt" | "stop" => Ok(FaultPolicy::Halt),
+            "ignore" | "skip" => Ok(FaultPolicy::Ignore),
+            "pause" | "debug" => Ok(FaultPolicy::Pause),
+            _ => Err(UnknownName::new("fault policy", s, "ignore, halt, pause")),
+        }
+    }
+}
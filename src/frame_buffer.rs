@@ -0,0 +1,369 @@
+/*
+All setting of pixels of this display are done through the use of sprites that are always 8 × N where N is the pixel height
+of the sprite. Chip8 comes with a font set (sprites) that allows character 0-9 and A-F to be printed directly to the
+screen. Each one of these characters fit within a 8x5 grid. SUPER-CHIP adds 16 × 16 sprites, which are stored as two bytes
+per row.
+*/
+
+pub const LORES_WIDTH: usize = 64;
+pub const LORES_HEIGHT: usize = 32;
+pub const HIRES_WIDTH: usize = 128;
+pub const HIRES_HEIGHT: usize = 64;
+pub const PLANES: usize = 2; // XO-CHIP bitplanes, giving 4 colours
+
+pub fn get_bit(value: &u8, position: &u8) -> bool { // from most to least significant
+    value & (1 << (7-position)) != 0
+}
+
+/*
+The framebuffer is a 64x32 bit memory array that is written two in 8-bit chunks by reading memory locations. A sprite's
+starting position always wraps around the screen. What happens to the part of a sprite that runs past the edge is a quirk:
+the VIP and SUPER-CHIP clip it, XO-CHIP wraps it around to the opposite edge, so rows are written from Y all the way until
+(Y + N) % 32.
+
+SUPER-CHIP's high resolution mode doubles this to 128x64. The buffer is always allocated at the high resolution and only the
+top left 64x32 corner is used in low resolution mode, unless lores pixels are doubled: SUPER-CHIP 1.1 draws every lores
+pixel as a 2x2 block of the hires buffer and scrolls in hires pixels, so a lores picture survives a switch to hires and
+an odd scroll moves it by half a lores pixel.
+
+XO-CHIP stacks two of these bitplanes, each pixel stores one bit per plane so a pixel's value is its colour index. FN01
+selects which planes drawing, clearing and scrolling affect; plain CHIP8 programs only ever touch plane 1.
+*/
+pub struct FrameBuffer {
+    pixels: [[u8; HIRES_WIDTH]; HIRES_HEIGHT], // 64 rows of 128, bit N is plane N+1
+    hires: bool,
+    planes: u8, // bitmask of the selected planes
+    clip: bool, // clip sprites at the edges instead of wrapping them around
+    doubled: bool, // lores pixels are 2x2 blocks of the whole hires buffer
+}
+
+impl FrameBuffer {
+    pub fn new(hires: bool) -> Self {
+        Self {
+            pixels: [[0; HIRES_WIDTH]; HIRES_HEIGHT],
+            hires,
+            planes: 1,
+            clip: true,
+            doubled: false,
+        }
+    }
+
+    /// Clip sprites at the screen edges, or wrap them around to the opposite edge
+    pub fn set_clipping(&mut self, clip: bool) {
+        self.clip = clip;
+    }
+
+    /// Draw lores pixels as 2x2 blocks of the hires buffer the way SUPER-CHIP 1.1 does, or into its top left corner
+    pub fn set_doubled(&mut self, doubled: bool) {
+        self.doubled = doubled;
+    }
+
+    pub fn width(&self) -> usize {
+        if self.hires { HIRES_WIDTH } else { LORES_WIDTH }
+    }
+
+    pub fn height(&self) -> usize {
+        if self.hires { HIRES_HEIGHT } else { LORES_HEIGHT }
+    }
+
+    pub fn hires(&self) -> bool {
+        self.hires
+    }
+
+    /// Size of the part of the buffer that is shown, which is the whole of it when lores pixels are doubled
+    pub fn size(&self) -> (usize, usize) {
+        let scale = self.scale();
+        (self.width() * scale, self.height() * scale)
+    }
+
+    // hires pixels per lores pixel along each side
+    fn scale(&self) -> usize {
+        if self.doubled && !self.hires { 2 } else { 1 }
+    }
+
+    /// Switch between 64x32 and 128x64. SUPER-CHIP 1.1 leaves the pixels where they are, XO-CHIP clears every plane.
+    /// With doubled lores pixels a lores picture shows up in hires at twice the size.
+    pub fn set_hires(&mut self, hires: bool, clear: bool) {
+        self.hires = hires;
+        if clear {
+            self.pixels = [[0; HIRES_WIDTH]; HIRES_HEIGHT];
+        }
+    }
+
+    pub fn select_planes(&mut self, planes: u8) {
+        self.planes = planes & ((1 << PLANES) - 1);
+    }
+
+    /// Number of selected planes, a sprite holds one copy of its rows for each of them.
+    pub fn selected_planes(&self) -> usize {
+        self.planes.count_ones() as usize
+    }
+
+    /// Clear the selected planes
+    pub fn clear(&mut self) {
+        for row in self.pixels.iter_mut() {
+            for pixel in row.iter_mut() {
+                *pixel &= !self.planes;
+            }
+        }
+    }
+
+    /// XOR a sprite that is `rows` high and `row_bytes` wide (8 pixels per byte) onto the screen, wrapping the starting
+    /// position and clipping or wrapping the rest. `sprite` holds the rows for each selected plane, lowest plane first.
+    /// Returns true if any pixel was turned off in any row on any of the selected planes.
+    pub fn draw_sprite(&mut self, x: usize, y: usize, rows: usize, row_bytes: usize, sprite: &[u8]) -> bool {
+        let x = x % self.width();
+        let y = y % self.height();
+
+        let mut vf_flip = false;
+
+        let planes = self.planes;
+        let selected = (0..PLANES as u8).map(|plane| 1 << plane).filter(|mask| planes & mask != 0);
+        for (plane, plane_sprite) in selected.zip(sprite.chunks(rows * row_bytes)) {
+            for (i, row) in plane_sprite.chunks(row_bytes).enumerate() {
+                if self.clip && y + i >= self.height() {
+                    break;
+                }
+                vf_flip |= self.set(plane, x, (y + i) % self.height(), row); // collisions from every row count
+            }
+        }
+
+        vf_flip
+    }
+
+    /// XOR one sprite row (8 pixels per byte) onto a plane, returns true if any pixel was turned off.
+    fn set(&mut self, plane: u8, x: usize, y: usize, row: &[u8]) -> bool {
+        let mut vf_flip = false;
+
+        for (byte_index, byte) in row.iter().enumerate() {
+            for i in 0..8 {
+                let mut column = x + byte_index * 8 + i as usize;
+                if column >= self.width() {
+                    if self.clip {
+                        break;
+                    }
+                    column %= self.width();
+                }
+                if get_bit(byte, &i) { // if the bit was on before and it's getting turned off, flip VF
+                    let scale = self.scale();
+                    for row in &mut self.pixels[y * scale..(y + 1) * scale] {
+                        for pixel in &mut row[column * scale..(column + 1) * scale] {
+                            vf_flip |= *pixel & plane != 0;
+                            *pixel ^= plane;
+                        }
+                    }
+                }
+            }
+        }
+
+        vf_flip
+    }
+
+    /// Scroll the selected planes of the shown area down by n rows, rows scrolled in from the top are blank.
+    pub fn scroll_down(&mut self, n: usize) {
+        let ((width, height), planes) = (self.size(), self.planes);
+        for y in (0..height).rev() {
+            for x in 0..width {
+                let moved = if y >= n { self.pixels[y - n][x] & planes } else { 0 };
+                self.pixels[y][x] = (self.pixels[y][x] & !planes) | moved;
+            }
+        }
+    }
+
+    /// Scroll the selected planes of the shown area up by n rows, rows scrolled in from the bottom are blank.
+    pub fn scroll_up(&mut self, n: usize) {
+        let ((width, height), planes) = (self.size(), self.planes);
+        for y in 0..height {
+            for x in 0..width {
+                let moved = if y + n < height { self.pixels[y + n][x] & planes } else { 0 };
+                self.pixels[y][x] = (self.pixels[y][x] & !planes) | moved;
+            }
+        }
+    }
+
+    /// Scroll the selected planes of the shown area right by n columns, columns scrolled in from the left are blank.
+    pub fn scroll_right(&mut self, n: usize) {
+        let ((width, height), planes) = (self.size(), self.planes);
+        for row in &mut self.pixels[..height] {
+            for x in (0..width).rev() {
+                let moved = if x >= n { row[x - n] & planes } else { 0 };
+                row[x] = (row[x] & !planes) | moved;
+            }
+        }
+    }
+
+    /// Scroll the selected planes of the shown area left by n columns, columns scrolled in from the right are blank.
+    pub fn scroll_left(&mut self, n: usize) {
+        let ((width, height), planes) = (self.size(), self.planes);
+        for row in &mut self.pixels[..height] {
+            for x in 0..width {
+                let moved = if x + n < width { row[x + n] & planes } else { 0 };
+                row[x] = (row[x] & !planes) | moved;
+            }
+        }
+    }
+
+    /// Plane 1 of the lores screen packed 8 pixels to a byte, the way the COSMAC VIP keeps its display buffer
+    pub fn pack(&self) -> Vec<u8> {
+        let scale = self.scale();
+        let mut packed = vec![0; LORES_WIDTH * LORES_HEIGHT / 8];
+        for y in 0..LORES_HEIGHT {
+            for x in 0..LORES_WIDTH {
+                if self.pixels[y * scale][x * scale] & 1 != 0 {
+                    packed[(y * LORES_WIDTH + x) / 8] |= 0x80 >> (x % 8);
+                }
+            }
+        }
+
+        packed
+    }
+
+    /// Replace plane 1 of the lores screen with a packed display buffer
+    pub fn unpack(&mut self, packed: &[u8]) {
+        let scale = self.scale();
+        for (y, row) in self.pixels[..LORES_HEIGHT * scale].iter_mut().enumerate() {
+            for (x, pixel) in row[..LORES_WIDTH * scale].iter_mut().enumerate() {
+                let (x, y) = (x / scale, y / scale);
+                let on = packed[(y * LORES_WIDTH + x) / 8] & (0x80 >> (x % 8)) != 0;
+                *pixel = (*pixel & !1) | on as u8;
+            }
+        }
+    }
+
+    /// Colour index of a pixel of the shown area, bit N is set if the pixel is on in plane N+1
+    pub fn get(&self, x: usize, y: usize) -> u8 {
+        self.pixels[y][x]
+    }
+
+    /// Colour indices of the shown area in row-major order.
+    pub fn export(&self) -> Vec<u8> {
+        let (width, height) = self.size();
+        let mut final_array = Vec::with_capacity(width * height);
+        for row in &self.pixels[..height] {
+            final_array.extend_from_slice(&row[..width]);
+        }
+
+        final_array
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lit(frame_buffer: &FrameBuffer) -> usize {
+        frame_buffer.export().iter().filter(|&&pixel| pixel != 0).count()
+    }
+
+    #[test]
+    fn wide_rows_clip_at_the_active_width() {
+        let mut frame_buffer = FrameBuffer::new(false);
+        assert!(!frame_buffer.draw_sprite(60, 0, 1, 2, &[0xFF, 0xFF]));
+        assert_eq!(lit(&frame_buffer), 4);
+
+        frame_buffer.set_hires(true, true);
+        assert!(!frame_buffer.draw_sprite(60, 0, 1, 2, &[0xFF, 0xFF]));
+        assert!(frame_buffer.draw_sprite(60, 0, 1, 1, &[0x80]));
+        assert_eq!(frame_buffer.export().len(), HIRES_WIDTH * HIRES_HEIGHT);
+        assert_eq!(frame_buffer.get(60, 0), 0);
+        assert_eq!(frame_buffer.get(75, 0), 1);
+    }
+
+    #[test]
+    fn sprites_wrap_around_without_clipping() {
+        let mut frame_buffer = FrameBuffer::new(false);
+        frame_buffer.set_clipping(false);
+        assert!(!frame_buffer.draw_sprite(62, 31, 2, 1, &[0xF0, 0xF0]));
+        assert_eq!(lit(&frame_buffer), 8);
+        assert_eq!(frame_buffer.get(1, 0), 1);
+        assert_eq!(frame_buffer.get(2, 0), 0);
+        assert_eq!(frame_buffer.get(63, 31), 1);
+
+        // the starting position wraps either way
+        frame_buffer.set_clipping(true);
+        assert!(frame_buffer.draw_sprite(64 + 62, 32 + 31, 2, 1, &[0xF0, 0xF0]));
+        assert_eq!(lit(&frame_buffer), 6);
+    }
+
+    #[test]
+    fn collisions_accumulate_across_rows() {
+        let mut frame_buffer = FrameBuffer::new(false);
+        frame_buffer.draw_sprite(0, 0, 1, 1, &[0x80]);
+
+        // the first row collides, the last one doesn't
+        assert!(frame_buffer.draw_sprite(0, 0, 3, 1, &[0x80, 0x80, 0x80]));
+    }
+
+    #[test]
+    fn scrolling_moves_pixels_and_blanks_the_edges() {
+        let mut frame_buffer = FrameBuffer::new(false);
+        frame_buffer.draw_sprite(0, 0, 1, 1, &[0x80]);
+        frame_buffer.draw_sprite(63, 31, 1, 1, &[0x80]);
+
+        frame_buffer.scroll_down(2);
+        assert_eq!(frame_buffer.get(0, 2), 1);
+        assert_eq!(frame_buffer.get(0, 0), 0);
+
+        frame_buffer.scroll_right(4);
+        assert_eq!(frame_buffer.get(4, 2), 1);
+
+        frame_buffer.scroll_left(4);
+        frame_buffer.scroll_up(2);
+        assert_eq!(frame_buffer.get(0, 0), 1);
+        assert_eq!(lit(&frame_buffer), 1);
+    }
+
+    #[test]
+    fn doubled_lores_pixels_survive_a_switch_to_hires() {
+        let mut frame_buffer = FrameBuffer::new(false);
+        frame_buffer.set_doubled(true);
+        assert_eq!(frame_buffer.size(), (HIRES_WIDTH, HIRES_HEIGHT));
+
+        // lores (3, 1) covers hires (6, 2) to (7, 3)
+        assert!(!frame_buffer.draw_sprite(3, 1, 1, 1, &[0x80]));
+        frame_buffer.set_hires(true, false);
+        assert_eq!(frame_buffer.get(6, 2), 1);
+        assert_eq!(frame_buffer.get(7, 3), 1);
+        assert_eq!(frame_buffer.get(3, 1), 0);
+        assert_eq!(frame_buffer.get(8, 2), 0);
+        assert_eq!(lit(&frame_buffer), 4);
+    }
+
+    #[test]
+    fn doubled_lores_scrolls_in_hires_pixels() {
+        let mut frame_buffer = FrameBuffer::new(false);
+        frame_buffer.set_doubled(true);
+        frame_buffer.draw_sprite(0, 0, 1, 1, &[0x80]);
+
+        // one hires row is half a lores pixel
+        frame_buffer.scroll_down(1);
+        assert_eq!(frame_buffer.get(0, 0), 0);
+        assert_eq!(frame_buffer.get(0, 1), 1);
+        assert_eq!(frame_buffer.get(0, 2), 1);
+        assert_eq!(frame_buffer.get(0, 3), 0);
+
+        // drawing the same lores pixel again only collides with the half still under it
+        assert!(frame_buffer.draw_sprite(0, 0, 1, 1, &[0x80]));
+        assert_eq!(lit(&frame_buffer), 4);
+    }
+
+    #[test]
+    fn planes_are_drawn_cleared_and_collide_together() {
+        let mut frame_buffer = FrameBuffer::new(false);
+        frame_buffer.select_planes(3);
+        assert_eq!(frame_buffer.selected_planes(), 2);
+
+        // plane 1 gets the first row, plane 2 the second
+        assert!(!frame_buffer.draw_sprite(0, 0, 1, 1, &[0xC0, 0x80]));
+        assert_eq!(frame_buffer.get(0, 0), 3);
+        assert_eq!(frame_buffer.get(1, 0), 1);
+
+        // only plane 2 collides
+        assert!(frame_buffer.draw_sprite(0, 0, 1, 1, &[0x00, 0x80]));
+
+        frame_buffer.draw_sprite(0, 0, 1, 1, &[0x00, 0x80]);
+        frame_buffer.select_planes(2);
+        frame_buffer.clear();
+        assert_eq!(frame_buffer.get(0, 0), 1);
+    }
+}
@@ -0,0 +1,377 @@
+/*
+CHIP8 instructions are two bytes long and stored big-endian, except XO-CHIP's F000 NNNN and MEGA-CHIP's 01NN NNNN
+which take their address from the two bytes after them. The first nibble picks the instruction family and the
+remaining three nibbles are either an address (NNN), a byte (NN), a nibble (N) or register indices (X and Y):
+
+    0x D 1 2 5
+      | | | |
+      | X Y N
+      |  \NN/
+      \ NNN /
+
+Decoding happens once per fetched opcode, the executor in `CHIP8` then matches on the `Instruction` it gets back.
+
+Variants of the interpreter that reuse opcodes for their own extensions get a decoder of their own, which is tried before
+the shared one in `decode_for`.
+*/
+
+use crate::platform::Platform;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    Sys { nnn: u16 },                // 0NNN - call machine code routine, only run in hybrid mode
+    ScrollDown { n: u8 },            // 00CN - SUPER-CHIP
+    ScrollUp { n: u8 },              // 00DN - XO-CHIP
+    Clear,                           // 00E0
+    Return,                          // 00EE
+    ScrollRight,                     // 00FB - SUPER-CHIP
+    ScrollLeft,                      // 00FC - SUPER-CHIP
+    Exit,                            // 00FD - SUPER-CHIP
+    Lores,                           // 00FE - SUPER-CHIP 64x32 mode
+    Hires,                           // 00FF - SUPER-CHIP 128x64 mode
+    Jump { nnn: u16 },               // 1NNN
+    Call { nnn: u16 },               // 2NNN
+    SkipEqImm { x: u8, nn: u8 },     // 3XNN
+    SkipNeImm { x: u8, nn: u8 },     // 4XNN
+    SkipEqReg { x: u8, y: u8 },      // 5XY0
+    StoreRange { x: u8, y: u8 },     // 5XY2 - XO-CHIP
+    LoadRange { x: u8, y: u8 },      // 5XY3 - XO-CHIP
+    LoadImm { x: u8, nn: u8 },       // 6XNN
+    AddImm { x: u8, nn: u8 },        // 7XNN
+    Move { x: u8, y: u8 },           // 8XY0
+    Or { x: u8, y: u8 },             // 8XY1
+    And { x: u8, y: u8 },            // 8XY2
+    Xor { x: u8, y: u8 },            // 8XY3
+    Add { x: u8, y: u8 },            // 8XY4
+    Sub { x: u8, y: u8 },            // 8XY5
+    ShiftRight { x: u8, y: u8 },     // 8XY6
+    SubReverse { x: u8, y: u8 },     // 8XY7
+    ShiftLeft { x: u8, y: u8 },      // 8XYE
+    SkipNeReg { x: u8, y: u8 },      // 9XY0
+    LoadIndex { nnn: u16 },          // ANNN
+    LoadLongIndex,                   // F000 NNNN - XO-CHIP, the address follows the opcode
+    JumpOffset { x: u8, nnn: u16 },  // BNNN
+    Random { x: u8, nn: u8 },        // CXNN
+    Draw { x: u8, y: u8, n: u8 },    // DXYN - DXY0 draws a 16x16 sprite
+    SkipKey { x: u8 },               // EX9E
+    SkipNotKey { x: u8 },            // EXA1
+    SelectPlanes { n: u8 },          // FN01 - XO-CHIP, N is a bitmask of planes
+    LoadAudio,                       // F002 - XO-CHIP audio pattern from the index register
+    LoadDelay { x: u8 },             // FX07
+    WaitKey { x: u8 },               // FX0A
+    SetDelay { x: u8 },              // FX15
+    SetSound { x: u8 },              // FX18
+    AddIndex { x: u8 },              // FX1E
+    LoadFont { x: u8 },              // FX29
+    LoadLargeFont { x: u8 },         // FX30 - SUPER-CHIP
+    SetPitch { x: u8 },              // FX3A - XO-CHIP
+    StoreBcd { x: u8 },              // FX33
+    StoreRegisters { x: u8 },        // FX55
+    LoadRegisters { x: u8 },         // FX65
+    StoreFlags { x: u8 },            // FX75 - SUPER-CHIP
+    LoadFlags { x: u8 },             // FX85 - SUPER-CHIP
+    CycleBackground,                 // 02A0 - CHIP-8X
+    AddNibbles { x: u8, y: u8 },     // 5XY1 - CHIP-8X
+    ColourZones { x: u8, y: u8 },    // BXY0 - CHIP-8X, zones in VX and V(X+1), colour in VY
+    ColourRows { x: u8, y: u8, n: u8 }, // BXYN - CHIP-8X, position in VX and V(X+1), colour in VY
+    SkipKey2 { x: u8 },              // EXF2 - CHIP-8X
+    SkipNotKey2 { x: u8 },           // EXF5 - CHIP-8X
+    OutputPort { x: u8 },            // FXF8 - CHIP-8X, FX03 - CHIP-8E
+    InputPort { x: u8 },             // FXFB - CHIP-8X, FXE3/FXE7 - CHIP-8E
+    Nop,                             // 00F2 - CHIP-8E
+    WaitDelay,                       // 0151 - CHIP-8E, wait for the delay timer to run out
+    Skip,                            // 0188 - CHIP-8E, skip the next instruction
+    SkipGreater { x: u8, y: u8 },    // 5XY1 - CHIP-8E
+    StoreRangeInc { x: u8, y: u8 },  // 5XY2 - CHIP-8E, like XO-CHIP's but moves the index register past the range
+    LoadRangeInc { x: u8, y: u8 },   // 5XY3 - CHIP-8E
+    JumpBack { nn: u8 },             // BBNN - CHIP-8E
+    JumpForward { nn: u8 },          // BFNN - CHIP-8E
+    SkipBytes { x: u8 },             // FX1B - CHIP-8E, skip VX bytes
+    DelayWait { x: u8 },             // FX4F - CHIP-8E, set the delay timer to VX and wait for it to run out
+    MegaOff,                         // 0010 - MEGA-CHIP
+    MegaOn,                          // 0011 - MEGA-CHIP
+    LoadMegaIndex { nn: u8 },        // 01NN NNNN - MEGA-CHIP, 24-bit address, the low 16 bits follow the opcode
+    LoadPalette { nn: u8 },          // 02NN - MEGA-CHIP, NN colours from the index register
+    SpriteWidth { nn: u8 },          // 03NN - MEGA-CHIP
+    SpriteHeight { nn: u8 },         // 04NN - MEGA-CHIP
+    ScreenAlpha { nn: u8 },          // 05NN - MEGA-CHIP
+    PlaySound { n: u8 },             // 060N - MEGA-CHIP, digitised sound at the index register, N = 0 loops
+    StopSound,                       // 0700 - MEGA-CHIP
+    SetBlendMode { n: u8 },          // 080N - MEGA-CHIP
+    CollisionColour { nn: u8 },      // 09NN - MEGA-CHIP
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeError {
+    pub opcode: u16,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown opcode {:04X}", self.opcode)
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+pub fn decode(opcode: u16) -> Result<Instruction, DecodeError> {
+    use Instruction::*;
+
+    let x = ((opcode >> 8) & 0xF) as u8;
+    let y = ((opcode >> 4) & 0xF) as u8;
+    let n = (opcode & 0xF) as u8;
+    let nn = (opcode & 0xFF) as u8;
+    let nnn = opcode & 0xFFF;
+
+    let instruction = match opcode >> 12 {
+        0x0 => match nnn {
+            0x0E0 => Clear,
+            0x0EE => Return,
+            _ => Sys { nnn },
+        },
+        0x1 => Jump { nnn },
+        0x2 => Call { nnn },
+        0x3 => SkipEqImm { x, nn },
+        0x4 => SkipNeImm { x, nn },
+        0x5 if n == 0 => SkipEqReg { x, y },
+        0x6 => LoadImm { x, nn },
+        0x7 => AddImm { x, nn },
+        0x8 => match n {
+            0x0 => Move { x, y },
+            0x1 => Or { x, y },
+            0x2 => And { x, y },
+            0x3 => Xor { x, y },
+            0x4 => Add { x, y },
+            0x5 => Sub { x, y },
+            0x6 => ShiftRight { x, y },
+            0x7 => SubReverse { x, y },
+            0xE => ShiftLeft { x, y },
+            _ => return Err(DecodeError { opcode }),
+        },
+        0x9 if n == 0 => SkipNeReg { x, y },
+        0xA => LoadIndex { nnn },
+        0xB => JumpOffset { x, nnn },
+        0xC => Random { x, nn },
+        0xD => Draw { x, y, n },
+        0xE => match nn {
+            0x9E => SkipKey { x },
+            0xA1 => SkipNotKey { x },
+            _ => return Err(DecodeError { opcode }),
+        },
+        0xF => match nn {
+            0x07 => LoadDelay { x },
+            0x0A => WaitKey { x },
+            0x15 => SetDelay { x },
+            0x18 => SetSound { x },
+            0x1E => AddIndex { x },
+            0x29 => LoadFont { x },
+            0x33 => StoreBcd { x },
+            0x55 => StoreRegisters { x },
+            0x65 => LoadRegisters { x },
+            _ => return Err(DecodeError { opcode }),
+        },
+        _ => return Err(DecodeError { opcode }),
+    };
+
+    Ok(instruction)
+}
+
+/// Decode an opcode for a specific platform, its extensions take priority over the shared instructions
+pub fn decode_for(platform: Platform, opcode: u16) -> Result<Instruction, DecodeError> {
+    let extension = match platform {
+        Platform::Chip8X => decode_chip8x(opcode),
+        Platform::Chip8E => decode_chip8e(opcode),
+        Platform::SuperChip => decode_schip(opcode),
+        Platform::XoChip => decode_xochip(opcode).or_else(|| decode_schip(opcode)),
+        Platform::MegaChip => decode_megachip(opcode).or_else(|| decode_schip(opcode)),
+        Platform::Chip8 | Platform::Chip10 => None,
+    };
+
+    extension.map_or_else(|| decode(opcode), Ok)
+}
+
+fn decode_chip8x(opcode: u16) -> Option<Instruction> {
+    use Instruction::*;
+
+    let x = ((opcode >> 8) & 0xF) as u8;
+    let y = ((opcode >> 4) & 0xF) as u8;
+    let n = (opcode & 0xF) as u8;
+    let nn = (opcode & 0xFF) as u8;
+
+    match opcode >> 12 {
+        0x0 if opcode == 0x02A0 => Some(CycleBackground),
+        0x5 if n == 1 => Some(AddNibbles { x, y }),
+        0xB if n == 0 => Some(ColourZones { x, y }),
+        0xB => Some(ColourRows { x, y, n }),
+        0xE if nn == 0xF2 => Some(SkipKey2 { x }),
+        0xE if nn == 0xF5 => Some(SkipNotKey2 { x }),
+        0xF if nn == 0xF8 => Some(OutputPort { x }),
+        0xF if nn == 0xFB => Some(InputPort { x }),
+        _ => None,
+    }
+}
+
+fn decode_chip8e(opcode: u16) -> Option<Instruction> {
+    use Instruction::*;
+
+    let x = ((opcode >> 8) & 0xF) as u8;
+    let y = ((opcode >> 4) & 0xF) as u8;
+    let n = (opcode & 0xF) as u8;
+    let nn = (opcode & 0xFF) as u8;
+
+    match opcode >> 12 {
+        0x0 => match opcode {
+            0x00ED => Some(Exit), // stop
+            0x00F2 => Some(Nop),
+            0x0151 => Some(WaitDelay),
+            0x0188 => Some(Skip),
+            _ => None,
+        },
+        0x5 => match n {
+            0x1 => Some(SkipGreater { x, y }),
+            0x2 => Some(StoreRangeInc { x, y }),
+            0x3 => Some(LoadRangeInc { x, y }),
+            _ => None,
+        },
+        0xB if x == 0xB => Some(JumpBack { nn }),
+        0xB if x == 0xF => Some(JumpForward { nn }),
+        0xF => match nn {
+            0x03 => Some(OutputPort { x }),
+            0x1B => Some(SkipBytes { x }),
+            0x4F => Some(DelayWait { x }),
+            0xE3 | 0xE7 => Some(InputPort { x }),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// also the base of XO-CHIP and MEGA-CHIP, on the VIP platforms these opcodes are 0NNN machine code calls
+fn decode_schip(opcode: u16) -> Option<Instruction> {
+    use Instruction::*;
+
+    let x = ((opcode >> 8) & 0xF) as u8;
+    let n = (opcode & 0xF) as u8;
+    let nn = (opcode & 0xFF) as u8;
+
+    match opcode >> 12 {
+        0x0 => match opcode {
+            0x00C0..=0x00CF => Some(ScrollDown { n }),
+            0x00FB => Some(ScrollRight),
+            0x00FC => Some(ScrollLeft),
+            0x00FD => Some(Exit),
+            0x00FE => Some(Lores),
+            0x00FF => Some(Hires),
+            _ => None,
+        },
+        0xF => match nn {
+            0x30 => Some(LoadLargeFont { x }),
+            0x75 => Some(StoreFlags { x }),
+            0x85 => Some(LoadFlags { x }),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn decode_xochip(opcode: u16) -> Option<Instruction> {
+    use Instruction::*;
+
+    let x = ((opcode >> 8) & 0xF) as u8;
+    let y = ((opcode >> 4) & 0xF) as u8;
+    let n = (opcode & 0xF) as u8;
+    let nn = (opcode & 0xFF) as u8;
+
+    match opcode >> 12 {
+        0x0 if opcode >> 4 == 0x00D => Some(ScrollUp { n }),
+        0x5 if n == 2 => Some(StoreRange { x, y }),
+        0x5 if n == 3 => Some(LoadRange { x, y }),
+        0xF if opcode == 0xF000 => Some(LoadLongIndex),
+        0xF if opcode == 0xF002 => Some(LoadAudio),
+        0xF if nn == 0x01 => Some(SelectPlanes { n: x }),
+        0xF if nn == 0x3A => Some(SetPitch { x }),
+        _ => None,
+    }
+}
+
+fn decode_megachip(opcode: u16) -> Option<Instruction> {
+    use Instruction::*;
+
+    let n = (opcode & 0xF) as u8;
+    let nn = (opcode & 0xFF) as u8;
+
+    match opcode >> 8 {
+        0x00 => match nn {
+            0x10 => Some(MegaOff),
+            0x11 => Some(MegaOn),
+            0xB0..=0xBF => Some(ScrollUp { n }),
+            _ => None,
+        },
+        0x01 => Some(LoadMegaIndex { nn }),
+        0x02 => Some(LoadPalette { nn }),
+        0x03 => Some(SpriteWidth { nn }),
+        0x04 => Some(SpriteHeight { nn }),
+        0x05 => Some(ScreenAlpha { nn }),
+        0x06 if nn < 0x10 => Some(PlaySound { n }),
+        0x07 if nn == 0x00 => Some(StopSound),
+        0x08 if nn < 0x10 => Some(SetBlendMode { n }),
+        0x09 => Some(CollisionColour { nn }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_operand_fields() {
+        assert_eq!(decode(0xD125), Ok(Instruction::Draw { x: 1, y: 2, n: 5 }));
+        assert_eq!(decode(0x2228), Ok(Instruction::Call { nnn: 0x228 }));
+        assert_eq!(decode(0x7A0F), Ok(Instruction::AddImm { x: 0xA, nn: 0x0F }));
+        assert_eq!(decode(0xBF00), Ok(Instruction::JumpOffset { x: 0xF, nnn: 0xF00 }));
+    }
+
+    #[test]
+    fn decodes_zero_prefixed_opcodes() {
+        assert_eq!(decode(0x00E0), Ok(Instruction::Clear));
+        assert_eq!(decode(0x00EE), Ok(Instruction::Return));
+        assert_eq!(decode(0x0123), Ok(Instruction::Sys { nnn: 0x123 }));
+        assert_eq!(decode(0x00FF), Ok(Instruction::Sys { nnn: 0x0FF }));
+        assert_eq!(decode_for(Platform::SuperChip, 0x00FF), Ok(Instruction::Hires));
+        assert_eq!(decode_for(Platform::SuperChip, 0x00C5), Ok(Instruction::ScrollDown { n: 5 }));
+        assert_eq!(decode_for(Platform::SuperChip, 0x00D5), Ok(Instruction::Sys { nnn: 0x0D5 }));
+        assert_eq!(decode_for(Platform::XoChip, 0x00D5), Ok(Instruction::ScrollUp { n: 5 }));
+        assert_eq!(decode_for(Platform::XoChip, 0x00FD), Ok(Instruction::Exit));
+    }
+
+    #[test]
+    fn platform_extensions_take_priority() {
+        assert_eq!(decode_for(Platform::SuperChip, 0xB120), Ok(Instruction::JumpOffset { x: 1, nnn: 0x120 }));
+        assert_eq!(decode_for(Platform::Chip8X, 0xB150), Ok(Instruction::ColourZones { x: 1, y: 5 }));
+        assert_eq!(decode_for(Platform::Chip8X, 0xBE53), Ok(Instruction::ColourRows { x: 0xE, y: 5, n: 3 }));
+        assert_eq!(decode_for(Platform::Chip8X, 0x02A0), Ok(Instruction::CycleBackground));
+        assert_eq!(decode_for(Platform::Chip8X, 0x00E0), Ok(Instruction::Clear));
+        assert_eq!(decode_for(Platform::Chip8, 0x5121), Err(DecodeError { opcode: 0x5121 }));
+        assert_eq!(decode_for(Platform::Chip8E, 0x5121), Ok(Instruction::SkipGreater { x: 1, y: 2 }));
+        assert_eq!(decode_for(Platform::Chip8E, 0xBB04), Ok(Instruction::JumpBack { nn: 4 }));
+        assert_eq!(decode_for(Platform::Chip8E, 0xB204), Ok(Instruction::JumpOffset { x: 2, nnn: 0x204 }));
+        assert_eq!(decode_for(Platform::XoChip, 0x5122), Ok(Instruction::StoreRange { x: 1, y: 2 }));
+        assert_eq!(decode_for(Platform::XoChip, 0xF000), Ok(Instruction::LoadLongIndex));
+        assert_eq!(decode_for(Platform::SuperChip, 0x5123), Err(DecodeError { opcode: 0x5123 }));
+        assert_eq!(decode_for(Platform::SuperChip, 0xF201), Err(DecodeError { opcode: 0xF201 }));
+        assert_eq!(decode_for(Platform::Chip8, 0xF275), Err(DecodeError { opcode: 0xF275 }));
+        assert_eq!(decode_for(Platform::MegaChip, 0xF275), Ok(Instruction::StoreFlags { x: 2 }));
+        assert_eq!(decode_for(Platform::MegaChip, 0x0112), Ok(Instruction::LoadMegaIndex { nn: 0x12 }));
+        assert_eq!(decode_for(Platform::MegaChip, 0x00B3), Ok(Instruction::ScrollUp { n: 3 }));
+        assert_eq!(decode_for(Platform::SuperChip, 0x0112), Ok(Instruction::Sys { nnn: 0x112 }));
+    }
+
+    #[test]
+    fn rejects_unknown_opcodes() {
+        for opcode in [0x5121, 0x5124, 0x8128, 0x9121, 0xE1FF, 0xF1FF, 0xF000, 0xF130] {
+            assert_eq!(decode(opcode), Err(DecodeError { opcode }));
+        }
+    }
+}
@@ -0,0 +1,280 @@
+/*
+Which keyboard keys stand in for the hex keypad. The default is the usual QWERTY layout:
+
+    1 2 3 4 | 1 2 3 C
+    Q W E R | 4 5 6 D
+    A S D F | 7 8 9 E
+    Z X C V | A 0 B F
+
+and <config dir>/chip8/keymap.cfg can change it. The file has a [keymap] section with one line per hex key, naming the
+keyboard keys that press it by their physical position (winit's `KeyCode` names, so "KeyQ" is the key left of "KeyW"
+whatever it's labelled), and [keymap <rom file name>] sections that override some of the keys for a single ROM:
+
+    [keymap]
+    1 = Digit1
+    4 = KeyQ, ArrowLeft
+
+    [keymap pong.ch8]
+    1 = ArrowUp
+    4 = ArrowDown
+
+Lines starting with # are comments. A section can't bind the same hex key or the same keyboard key twice, which is
+checked in the sections for other ROMs too. An override replaces the keyboard keys of the hex keys it lists and takes
+its keyboard keys away from whatever hex keys they had.
+*/
+
+use crate::config::config_dir;
+use crate::keypad::KEYS;
+use std::{fmt, fs, io, path::Path};
+use winit::keyboard::KeyCode;
+
+const SECTION: &str = "keymap";
+
+pub const QWERTY: [(KeyCode, u8); 16] = [
+    (KeyCode::Digit1, 0x1), (KeyCode::Digit2, 0x2), (KeyCode::Digit3, 0x3), (KeyCode::Digit4, 0xC),
+    (KeyCode::KeyQ, 0x4), (KeyCode::KeyW, 0x5), (KeyCode::KeyE, 0x6), (KeyCode::KeyR, 0xD),
+    (KeyCode::KeyA, 0x7), (KeyCode::KeyS, 0x8), (KeyCode::KeyD, 0x9), (KeyCode::KeyF, 0xE),
+    (KeyCode::KeyZ, 0xA), (KeyCode::KeyX, 0x0), (KeyCode::KeyC, 0xB), (KeyCode::KeyV, 0xF),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keymap {
+    bindings: Vec<(KeyCode, u8)>, // keyboard key and the hex key it presses
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self {
+            bindings: QWERTY.to_vec(),
+        }
+    }
+}
+
+impl Keymap {
+    /// The keymap from the config file with the overrides for `rom_name`, the default if there's no config file
+    pub fn load(rom_name: &str) -> Result<Self, KeymapError> {
+        let Some(dir) = config_dir() else {
+            return Ok(Self::default());
+        };
+
+        Self::load_from(&dir.join("chip8").join("keymap.cfg"), rom_name)
+    }
+
+    fn load_from(path: &Path, rom_name: &str) -> Result<Self, KeymapError> {
+        match fs::read_to_string(path) {
+            Ok(config) => Self::parse(&config, rom_name),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(KeymapError::new(0, format!("couldn't read {}: {err}", path.display()))),
+        }
+    }
+
+    /// Parse a config file, applying the overrides for `rom_name` on top of the [keymap] section
+    pub fn parse(config: &str, rom_name: &str) -> Result<Self, KeymapError> {
+        let mut keymap = Self::default();
+        let mut overrides = Vec::new();
+        let mut section = Section::Other;
+        let mut seen_hex = Vec::new();
+        let mut seen_keys = Vec::new();
+
+        for (number, line) in config.lines().enumerate().map(|(i, line)| (i + 1, line.trim())) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(header) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+                section = match header.trim().split_once(char::is_whitespace) {
+                    None if header.trim() == SECTION => Section::Keymap,
+                    Some((SECTION, rom)) if rom.trim() == rom_name => Section::Rom,
+                    Some((SECTION, _)) => Section::OtherRom,
+                    _ => Section::Other,
+                };
+                if section == Section::Keymap {
+                    keymap.bindings.clear();
+                }
+                seen_hex.clear();
+                seen_keys.clear();
+                continue;
+            }
+
+            if section == Section::Other {
+                continue;
+            }
+
+            let Some((hex, keys)) = line.split_once('=') else {
+                return Err(KeymapError::new(number, format!("expected 'hex key = keyboard keys', found '{line}'")));
+            };
+            let hex = hex.trim();
+            let Some(hex) = u8::from_str_radix(hex, 16).ok().filter(|_| hex.len() == 1) else {
+                return Err(KeymapError::new(number, format!("'{hex}' isn't a hex key, expected 0-F")));
+            };
+            if seen_hex.contains(&hex) {
+                return Err(KeymapError::new(number, format!("hex key {hex:X} is bound twice")));
+            }
+            seen_hex.push(hex);
+
+            for name in keys.split(',').map(str::trim) {
+                let Some(key) = key_code(name) else {
+                    return Err(KeymapError::new(number, format!("unknown key '{name}'")));
+                };
+                if seen_keys.contains(&key) {
+                    return Err(KeymapError::new(number, format!("key '{name}' is bound twice")));
+                }
+                seen_keys.push(key);
+
+                match section {
+                    Section::Keymap => keymap.bindings.push((key, hex)),
+                    Section::Rom => overrides.push((key, hex)),
+                    _ => {},
+                }
+            }
+        }
+
+        for &(key, hex) in &overrides {
+            keymap.bindings.retain(|&(bound, bound_hex)| bound != key && bound_hex != hex);
+        }
+        keymap.bindings.extend(overrides);
+
+        Ok(keymap)
+    }
+
+    pub fn bindings(&self) -> &[(KeyCode, u8)] {
+        &self.bindings
+    }
+
+    /// Which hex keys are held given which keyboard keys are down, a hex key is held while any of its keys is
+    pub fn held(&self, is_down: impl Fn(KeyCode) -> bool) -> [bool; KEYS] {
+        let mut held = [false; KEYS];
+        for &(key, hex) in &self.bindings {
+            held[hex as usize % KEYS] |= is_down(key);
+        }
+
+        held
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Section {
+    Keymap,   // [keymap]
+    Rom,      // [keymap <rom>] for the running ROM
+    OtherRom, // [keymap <rom>] for another ROM, checked but not applied
+    Other,    // anything else, skipped
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeymapError {
+    pub line: usize, // 0 when the file couldn't be read at all
+    pub message: String,
+}
+
+impl KeymapError {
+    fn new(line: usize, message: String) -> Self {
+        Self { line, message }
+    }
+}
+
+impl fmt::Display for KeymapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "keymap: {}", self.message)
+        } else {
+            write!(f, "keymap line {}: {}", self.line, self.message)
+        }
+    }
+}
+
+impl std::error::Error for KeymapError {}
+
+/// Keyboard key from its `KeyCode` name, the keys a keypad is likely to be mapped to
+fn key_code(name: &str) -> Option<KeyCode> {
+    use KeyCode::*;
+
+    let key = match name {
+        "Digit0" => Digit0, "Digit1" => Digit1, "Digit2" => Digit2, "Digit3" => Digit3, "Digit4" => Digit4,
+        "Digit5" => Digit5, "Digit6" => Digit6, "Digit7" => Digit7, "Digit8" => Digit8, "Digit9" => Digit9,
+        "KeyA" => KeyA, "KeyB" => KeyB, "KeyC" => KeyC, "KeyD" => KeyD, "KeyE" => KeyE, "KeyF" => KeyF,
+        "KeyG" => KeyG, "KeyH" => KeyH, "KeyI" => KeyI, "KeyJ" => KeyJ, "KeyK" => KeyK, "KeyL" => KeyL,
+        "KeyM" => KeyM, "KeyN" => KeyN, "KeyO" => KeyO, "KeyP" => KeyP, "KeyQ" => KeyQ, "KeyR" => KeyR,
+        "KeyS" => KeyS, "KeyT" => KeyT, "KeyU" => KeyU, "KeyV" => KeyV, "KeyW" => KeyW, "KeyX" => KeyX,
+        "KeyY" => KeyY, "KeyZ" => KeyZ,
+        "Numpad0" => Numpad0, "Numpad1" => Numpad1, "Numpad2" => Numpad2, "Numpad3" => Numpad3,
+        "Numpad4" => Numpad4, "Numpad5" => Numpad5, "Numpad6" => Numpad6, "Numpad7" => Numpad7,
+        "Numpad8" => Numpad8, "Numpad9" => Numpad9, "NumpadAdd" => NumpadAdd, "NumpadSubtract" => NumpadSubtract,
+        "NumpadMultiply" => NumpadMultiply, "NumpadDivide" => NumpadDivide, "NumpadDecimal" => NumpadDecimal,
+        "NumpadEnter" => NumpadEnter,
+        "ArrowUp" => ArrowUp, "ArrowDown" => ArrowDown, "ArrowLeft" => ArrowLeft, "ArrowRight" => ArrowRight,
+        "Backquote" => Backquote, "Minus" => Minus, "Equal" => Equal, "BracketLeft" => BracketLeft,
+        "BracketRight" => BracketRight, "Backslash" => Backslash, "Semicolon" => Semicolon, "Quote" => Quote,
+        "Comma" => Comma, "Period" => Period, "Slash" => Slash,
+        "Enter" => Enter, "Tab" => Tab, "Backspace" => Backspace,
+        "ShiftLeft" => ShiftLeft, "ShiftRight" => ShiftRight, "ControlLeft" => ControlLeft,
+        "ControlRight" => ControlRight, "AltLeft" => AltLeft, "AltRight" => AltRight,
+        _ => return None,
+    };
+
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_config_is_qwerty() {
+        assert_eq!(Keymap::parse("", "pong.ch8"), Ok(Keymap::default()));
+        assert_eq!(Keymap::parse("[other]\n1 = Nonsense\n", "pong.ch8"), Ok(Keymap::default()));
+    }
+
+    #[test]
+    fn keymap_section_and_rom_overrides() {
+        let config = "
+            # AZERTY
+            [keymap]
+            1 = Digit1
+            4 = KeyQ, ArrowLeft
+            5 = KeyW
+
+            [keymap pong.ch8]
+            1 = ArrowUp
+            5 = ArrowLeft
+
+            [keymap tetris.ch8]
+            1 = KeyT
+        ";
+
+        let keymap = Keymap::parse(config, "other.ch8").unwrap();
+        assert_eq!(keymap.bindings(), [
+            (KeyCode::Digit1, 0x1), (KeyCode::KeyQ, 0x4), (KeyCode::ArrowLeft, 0x4), (KeyCode::KeyW, 0x5),
+        ]);
+
+        let keymap = Keymap::parse(config, "pong.ch8").unwrap();
+        assert_eq!(keymap.bindings(), [
+            (KeyCode::KeyQ, 0x4), (KeyCode::ArrowUp, 0x1), (KeyCode::ArrowLeft, 0x5),
+        ]);
+    }
+
+    #[test]
+    fn hex_keys_stay_held_while_any_of_their_keys_is_down() {
+        let keymap = Keymap::parse("[keymap]\n4 = KeyQ, ArrowLeft\n", "pong.ch8").unwrap();
+        let held = keymap.held(|key| key == KeyCode::ArrowLeft);
+        assert!(held[0x4]);
+        assert_eq!(held.iter().filter(|&&down| down).count(), 1);
+        assert!(!keymap.held(|key| key == KeyCode::KeyW)[0x4]);
+    }
+
+    #[test]
+    fn rejects_duplicate_and_unknown_keys() {
+        let error = |config| Keymap::parse(config, "pong.ch8").unwrap_err();
+
+        assert_eq!(error("[keymap]\n1 = KeyQ\n2 = KeyQ\n").line, 3);
+        assert_eq!(error("[keymap]\n1 = KeyQ\n1 = KeyW\n").line, 3);
+        assert_eq!(error("[keymap]\n1 = KeyQ, KeyQ\n").line, 2);
+        assert_eq!(error("[keymap]\n1 = Kay\n").message, "unknown key 'Kay'");
+        assert_eq!(error("[keymap]\nG = KeyG\n").message, "'G' isn't a hex key, expected 0-F");
+        assert_eq!(error("[keymap]\n10 = KeyG\n").line, 2);
+        assert_eq!(error("[keymap pong.ch8]\nKeyQ\n").line, 2);
+
+        // sections for other ROMs are checked too
+        assert_eq!(error("[keymap tetris.ch8]\n1 = Kay\n").line, 2);
+        assert_eq!(error("[keymap tetris.ch8]\n1 = KeyQ\n2 = KeyQ\n").line, 3);
+    }
+}
@@ -0,0 +1,110 @@
+/*
+The COSMAC VIP's hex keypad, laid out like this:
+
+    1 2 3 C
+    4 5 6 D
+    7 8 9 E
+    A 0 B F
+
+The frontend feeds key down and key up events in, the instructions only ever look at which keys are held. FX0A is the
+exception: the VIP interpreter waited for a key to go down and then for it to come back up before it carried on, so the
+keypad remembers the first key released while an FX0A is waiting.
+*/
+
+pub const KEYS: usize = 16;
+
+pub struct Keypad {
+    held: [bool; KEYS],
+    waiting: bool,          // an FX0A is waiting for a key
+    released: Option<u8>,   // first key released while waiting
+}
+
+impl Keypad {
+    pub fn new() -> Self {
+        Self {
+            held: [false; KEYS],
+            waiting: false,
+            released: None,
+        }
+    }
+
+    pub fn press(&mut self, key: u8) {
+        self.held[key as usize % KEYS] = true;
+    }
+
+    pub fn release(&mut self, key: u8) {
+        let key = key % KEYS as u8;
+        if self.waiting && self.held[key as usize] && self.released.is_none() {
+            self.released = Some(key);
+        }
+        self.held[key as usize] = false;
+    }
+
+    /// Press and release keys so that exactly the ones in `held` are down
+    pub fn set_held(&mut self, held: [bool; KEYS]) {
+        for (key, &down) in held.iter().enumerate() {
+            if down {
+                self.press(key as u8);
+            } else if self.held[key] {
+                self.release(key as u8);
+            }
+        }
+    }
+
+    pub fn is_held(&self, key: u8) -> bool {
+        self.held[key as usize % KEYS]
+    }
+
+    /// FX0A, called every time the instruction runs. Returns the key once one has been pressed and released.
+    pub fn wait_for_key(&mut self) -> Option<u8> {
+        if !self.waiting {
+            self.waiting = true;
+            self.released = None;
+        }
+
+        let key = self.released.take();
+        self.waiting = key.is_none();
+        key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_stay_held_until_released() {
+        let mut keypad = Keypad::new();
+        keypad.press(0xA);
+        assert!(keypad.is_held(0xA));
+        assert!(!keypad.is_held(0xB));
+
+        keypad.release(0xA);
+        assert!(!keypad.is_held(0xA));
+
+        let mut held = [false; KEYS];
+        held[0xB] = true;
+        keypad.set_held(held);
+        assert!(keypad.is_held(0xB));
+        keypad.set_held([false; KEYS]);
+        assert!(!keypad.is_held(0xB));
+    }
+
+    #[test]
+    fn waiting_needs_a_press_and_a_release() {
+        let mut keypad = Keypad::new();
+        keypad.press(0x3); // released before anything waited
+        keypad.release(0x3);
+        assert_eq!(keypad.wait_for_key(), None);
+
+        keypad.press(0x5);
+        assert_eq!(keypad.wait_for_key(), None);
+        keypad.release(0x5);
+        assert_eq!(keypad.wait_for_key(), Some(0x5));
+
+        // the next wait starts over
+        keypad.press(0x6);
+        keypad.release(0x6);
+        assert_eq!(keypad.wait_for_key(), None);
+    }
+}
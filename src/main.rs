@@ -5,110 +5,110 @@
 #![forbid(unsafe_code)]
 #![allow(dead_code)]
 
+mod audio;
+mod cdp1802;
+mod chip8x;
+mod config;
+mod error;
+mod frame_buffer;
+mod instruction;
+mod keymap;
+mod keypad;
+mod megachip;
+mod name;
+mod platform;
+mod quirks;
+mod rpl;
+mod scheduler;
+mod timing;
+
 use error_iter::ErrorIter as _;
-use log::error;
+use log::{debug, error, trace, warn};
 use pixels::{Error, Pixels, SurfaceTexture};
 use winit::dpi::LogicalSize;
 use winit::event::{Event, WindowEvent};
-use winit::event_loop::EventLoop;
+use winit::event_loop::{ControlFlow, EventLoop};
 use winit::keyboard::KeyCode;
 use winit::window::WindowBuilder;
 use winit_input_helper::WinitInputHelper;
-use std::{fs, time::Instant};
-use rand::Rng;
-
-// Chip 8 resolution is 64x32 so we upscale this by a factor of k
+use std::{fs, ops::Range, path::Path, time::{Duration, Instant}};
+use audio::{Audio, PATTERN_BYTES};
+use cdp1802::{Cdp1802, VIP_DISPLAY, VIP_REGISTERS, VIP_STACK};
+use chip8x::Chip8X;
+use error::{Chip8Error, FaultPolicy};
+use frame_buffer::{get_bit, FrameBuffer};
+use instruction::{decode_for, Instruction};
+use keymap::Keymap;
+use keypad::Keypad;
+use megachip::{BlendMode, MegaChip, MEGA_HEIGHT, MEGA_WIDTH};
+use platform::Platform;
+use quirks::Quirks;
+use rpl::RplFlags;
+use scheduler::{Scheduler, SystemClock};
+use timing::{vip_cycles, Timing, VIP_FETCH_CYCLES, VIP_INTERPRETER_CYCLES};
+
+// Chip 8 resolution is 64x32 (128x64 in SUPER-CHIP hires mode) so we upscale this by a factor of k
 const K: u32 = 4; // upscaling factor
-const WIDTH: u32 = 64;
-const HEIGHT: u32 = 32;
 const INSTRUCTIONS_PER_SECOND: usize = 700; // the amount of instructions to execute per second
-
-const SUPER_CHIP: bool = true; // if ROM doesn't work, try messing around with this 
-
-/*
-All setting of pixels of this display are done through the use of sprites that are always 8 × N where N is the pixel height
-of the sprite. Chip8 comes with a font set (sprites) that allows character 0-9 and A-F to be printed directly to the
-screen. Each one of these characters fit within a 8x5 grid.
-*/
-
-fn get_bit(value: &u8, position: &u8) -> bool { // from most to least significant
-    value & (1 << (7-position)) != 0
-}
-
-/*
-The framebuffer is a 64x32 bit memory array that is written two in 8-bit chunks by reading memory locations. The framebuffer
-will feature a wraparound that causes the pixels to be written from the position Y + 0 in the y axis, all the way until (Y +N)%32.
-This will allow for proper wraparound of the sprites that need to be drawn.
-*/
-struct FrameBuffer {
-    pixels: [[bool; 64]; 32] // 32 rows of 64
-}
-
-impl FrameBuffer {
-    fn new() -> Self {
-        Self {
-            pixels: [[false; 64]; 32],
-        }
-    }
-
-    fn clear(&mut self) {
-        self.pixels = [[false; 64]; 32];
-    }
-
-    fn set(&mut self, x: u8, y: u8, value: u8) -> bool {
-        let mut vf_flip = false;
-
-        for i in 0..8 {
-            if x + i > 63 {
-                break;
-            }
-            let bit_on = get_bit(&value, &i);
-            if bit_on { // if the bit was on before and it's getting turned off, flip VF
-                if self.pixels[y as usize][(x + i) as usize] {
-                    vf_flip = true; 
-                }
-                self.pixels[y as usize][(x + i) as usize] ^= true;
-            }
-        }
-        
-        vf_flip
-    }
-
-    fn export(&self) -> [bool; 2048] {
-        let mut final_array = [false; 2048];
-        for j in 0..HEIGHT {
-            for i in 0..WIDTH {
-                final_array[(j*WIDTH+i) as usize] = self.pixels[j as usize][i as usize];
-            }
-        }
-
-        final_array
-    }
+const MACHINE_CODE_STEP_LIMIT: usize = 1_000_000; // 1802 instructions a 0NNN subroutine may run before it's abandoned
+
+// colour of each combination of XO-CHIP planes: off, plane 1, plane 2, both
+const PALETTE: [[u8; 4]; 4] = [
+    [0x00, 0x00, 0x00, 0xff],
+    [0xff, 0xff, 0xff, 0xff],
+    [0xaa, 0xaa, 0xaa, 0xff],
+    [0x55, 0x55, 0x55, 0xff],
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Running,
+    Paused,
+    Halted, // the program exited through 00FD, nothing runs after this
 }
 
 // Representation of the application state. In this example, a box will bounce around the screen.
 struct CHIP8 {
     registers: [u8; 16],
-    memory: [u8; 4096],  // index 512 (0x200) to 4095 are the program memory, 0x00 to 0x80 is
+    memory: Vec<u8>,     // 4 KB (XO-CHIP: 64 KB, MEGA-CHIP: 16 MB), index 512 (0x200, CHIP-8X: 0x300) onwards is the program memory, 0x00 to 0xF0 is
                          // supposed to be the default font storage
-    stack: Vec<u16>,     // 64 byte stack
+    stack: Vec<u16>,     // return addresses of 2NNN calls, fixed at the platform's depth
 
     pc: u16,             // Program Counter
-    sp: u16,             // Stack Pointer
-    index_reg: u16,
-    current_op: String,     // Current OP Code
+    sp: usize,           // Stack Pointer, the next free entry of the stack
+    index_reg: u32,      // 16 bits on XO-CHIP, 24 bits on MEGA-CHIP
+    current_op: u16,     // Current OP Code
     
     sound_timer: u8,
     delay_timer: u8,
 
+    audio: Audio,        // XO-CHIP pattern and pitch, plays while the sound timer runs
+
     frame_buffer: FrameBuffer,
+    megachip: MegaChip,  // MEGA-CHIP colour screen, replaces the frame buffer while it's enabled
+
+    platform: Platform,  // which member of the CHIP8 family is being emulated
+    hybrid: bool,        // run 0NNN machine code subroutines on the 1802 core instead of ignoring them
+    chip8x: Chip8X,      // CHIP-8X colour board and second keypad
+    port_output: u8,     // last value written to the VIP's I/O port (CHIP-8X: the VP-595 tone)
+    port_input: u8,      // value read from the VIP's I/O port
+    waiting_for_delay: bool, // CHIP-8E is stalled until the delay timer runs out
+    vblank: bool,        // a vertical blank happened since the last sprite was drawn
+    waiting_for_vblank: bool, // DXYN is stalled until the next frame (display wait quirk)
+    quirks: Quirks,      // behaviour of the interpreter the ROM was written for
+
+    rpl_flags: RplFlags, // SUPER-CHIP user flags, persisted between runs by the frontend
+
+    state: State,
 
-    paused: bool,
+    keypad: Keypad,
 
-    key_pressed: bool,
-    last_key: Option<u8>,
+    scheduler: Scheduler, // runs instructions and ticks the timers in 60 Hz frames
+    cycles: u64,          // VIP machine cycles run so far
+    cycle_overrun: u64,   // cycles the last frame ran past its budget, taken out of the next one
 
-    last_instant: Instant,
+    fault_policy: FaultPolicy, // what to do when the program faults
+    last_fault: Option<Chip8Error>,
 }
 
 /* Font
@@ -154,7 +154,33 @@ fn get_character_sprite(c: char) -> [u8; 5] {
     }
 }
 
-fn load_font_into_memory(memory: &mut [u8; 4096]) {
+// SUPER-CHIP 8x10 font for the digits, the letters are the ones XO-CHIP (Octo) added
+fn get_large_character_sprite(c: char) -> [u8; 10] {
+    match c {
+        '0' => [0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF],
+        '1' => [0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF],
+        '2' => [0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF],
+        '3' => [0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF],
+        '4' => [0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03],
+        '5' => [0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF],
+        '6' => [0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF],
+        '7' => [0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18],
+        '8' => [0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF],
+        '9' => [0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF],
+        'A' => [0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3],
+        'B' => [0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC],
+        'C' => [0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C],
+        'D' => [0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC],
+        'E' => [0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF],
+        'F' => [0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0],
+        _ => [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+    }
+}
+
+// the large font is stored straight after the 16 small characters
+const LARGE_FONT_START: u32 = 16 * 5;
+
+fn load_font_into_memory(memory: &mut [u8]) {
     let mut i = 0;
     for c in '0'..='9' {
         for j in get_character_sprite(c) {
@@ -169,396 +195,680 @@ fn load_font_into_memory(memory: &mut [u8; 4096]) {
             i += 1;
         }
     }
+
+    for c in ('0'..='9').chain('A'..='F') {
+        for j in get_large_character_sprite(c) {
+            memory[i] = j;
+            i += 1;
+        }
+    }
+}
+
+fn load_program_into_memory(memory: &mut [u8], start: usize, program: Vec<u8>) -> Result<(), Chip8Error> {
+    let max = memory.len() - start;
+    if program.len() > max {
+        return Err(Chip8Error::RomTooLarge { size: program.len(), max });
+    }
+
+    memory[start..start + program.len()].copy_from_slice(&program);
+    Ok(())
 }
 
-fn load_program_into_memory(memory: &mut [u8; 4096], program: Vec<u8>) {
-    let mut index: usize = 512;
-    for i in program {
-        memory[index] = i;
-        index += 1;
+// registers X through Y, counting down if Y is below X
+fn register_range(x: u8, y: u8) -> Box<dyn Iterator<Item = usize>> {
+    if x <= y {
+        Box::new(x as usize..=y as usize)
+    } else {
+        Box::new((y as usize..=x as usize).rev())
     }
 }
 
 impl CHIP8 {
-    /// Create a new emulator
-    fn new(program: Vec<u8>) -> Self {
-        let mut memory: [u8; 4096] = [0; 4096];
+    /// Create a new emulator, fails if the program doesn't fit in the platform's memory
+    fn new(program: Vec<u8>, platform: Platform, quirks: Quirks) -> Result<Self, Chip8Error> {
+        let mut memory = vec![0; platform.memory_size()];
 
         load_font_into_memory(&mut memory);
-        load_program_into_memory(&mut memory, program);
+        load_program_into_memory(&mut memory, platform.program_start() as usize, program)?;
+
+        let mut frame_buffer = FrameBuffer::new(platform.fixed_hires());
+        frame_buffer.set_clipping(quirks.clip_sprites);
+        frame_buffer.set_doubled(quirks.half_pixel_scroll);
 
-        Self {
+        Ok(Self {
             registers: [0; 16],
             memory,
 
-            pc: 512,
+            pc: platform.program_start(),
             sp: 0,
             index_reg: 0,
-            stack: vec![0; 32],
-            current_op: String::from(""),
+            stack: vec![0; platform.stack_depth()],
+            current_op: 0,
 
             sound_timer: 0,
             delay_timer: 0,
 
-            frame_buffer: FrameBuffer::new(),
+            audio: Audio::new(),
 
-            paused: false,
+            frame_buffer,
+            megachip: MegaChip::new(),
 
-            key_pressed: false,
-            last_key: None,
+            platform,
+            hybrid: false,
+            chip8x: Chip8X::new(),
+            port_output: 0,
+            port_input: 0,
+            waiting_for_delay: false,
+            vblank: true,
+            waiting_for_vblank: false,
+            quirks,
 
-            last_instant: Instant::now(),
-        }
+            rpl_flags: RplFlags::new(platform.rpl_flags()),
+
+            state: State::Running,
+
+            keypad: Keypad::new(),
+
+            scheduler: Scheduler::new(Box::new(SystemClock::new()), Scheduler::budget_for(INSTRUCTIONS_PER_SECOND)),
+            cycles: 0,
+            cycle_overrun: 0,
+
+            fault_policy: FaultPolicy::default(),
+            last_fault: None,
+        })
     }
 
     fn pause(&mut self) {
-        self.paused ^= true;
+        self.state = match self.state {
+            State::Running => State::Paused,
+            State::Paused => {
+                self.last_fault = None; // the fault didn't stop the program after all
+                State::Running
+            },
+            State::Halted => State::Halted,
+        };
     }
 
-    /// Update the `World` internal state; bounce the box around the screen.
-    fn update(&mut self) {
-        // fetch decode execute
-        
+    fn halted(&self) -> bool {
+        self.state == State::Halted
+    }
+
+    /// Run the frames that have come due since the last update, returns how many ran
+    fn update(&mut self) -> u32 {
+        let frames = self.scheduler.frames_due();
+        if self.state != State::Running {
+            return 0;
+        }
+
+        for _ in 0..frames {
+            self.run_frame();
+        }
+        frames
+    }
+
+    /// One 60 Hz frame: the frame's budget of instructions, then one timer tick
+    fn run_frame(&mut self) {
+        self.vblank();
+
+        let start = self.cycles;
+        match self.scheduler.timing {
+            Timing::Instructions => {
+                for _ in 0..self.scheduler.instructions_per_frame {
+                    if self.state != State::Running || self.waiting_for_vblank {
+                        break;
+                    }
+                    self.step_or_fault();
+                }
+            },
+            Timing::CosmacVip => { // run until the cycles left over from the display interrupt are used up
+                let budget = (VIP_INTERPRETER_CYCLES as u64).saturating_sub(self.cycle_overrun);
+                while self.state == State::Running && !self.waiting_for_vblank && self.cycles - start < budget {
+                    self.step_or_fault();
+                }
+                self.cycle_overrun = (self.cycles - start).saturating_sub(budget);
+            },
+        }
+        self.scheduler.record_cycles((self.cycles - start) as u32);
+
+        self.tick_timers();
+    }
+
+    /// The display interrupt at the start of a frame, lets a DXYN stalled by the display wait quirk run. Anything
+    /// driving `step` directly has to call this once a frame or lores sprites stop being drawn.
+    fn vblank(&mut self) {
+        self.vblank = true;
+        self.waiting_for_vblank = false;
+    }
+
+    /// Fetch the opcode at the program counter, decode it and execute it. An instruction that faults is left
+    /// unexecuted with the program counter still pointing at it.
+    fn step(&mut self) -> Result<(), Chip8Error> {
+        if self.state != State::Running {
+            return Ok(());
+        }
+
+        if self.waiting_for_delay {
+            self.waiting_for_delay = self.delay_timer > 0;
+            self.cycles += VIP_FETCH_CYCLES as u64;
+            return Ok(());
+        }
+
         // fetch
-        
-        if self.paused {
-            return;
+        self.current_op = self.read_opcode(self.pc)?;
+
+        // decode
+        let Ok(instruction) = decode_for(self.platform, self.current_op) else {
+            self.cycles += VIP_FETCH_CYCLES as u64;
+            return Err(Chip8Error::UnknownOpcode { opcode: self.current_op, pc: self.pc });
+        };
+
+        // execute, the cycles are charged once the instruction retires so a stalled or faulting one isn't paid twice
+        let cycles = vip_cycles(&instruction, &self.registers) as u64;
+        self.process_op(instruction)?;
+        if !self.waiting_for_vblank {
+            self.cycles += cycles;
         }
+        Ok(())
+    }
 
-        self.current_op = format!("{:X}", self.memory[self.pc as usize]);
-        // println!("BEFORE: {}", self.current_op);
-        if self.current_op.len() == 1 {
-            self.current_op = format!("0{}", self.current_op);
+    // step, handing any fault to the fault policy
+    fn step_or_fault(&mut self) {
+        if let Err(err) = self.step() {
+            self.fault(err);
         }
-        self.current_op = format!("{}{:X}", self.current_op, self.memory[(self.pc + 1) as usize]);
-        if self.current_op.len() == 3 {
-            self.current_op.insert(2, '0');
+    }
+
+    // `last_fault` is only kept while the fault is what stopped the program
+    fn fault(&mut self, err: Chip8Error) {
+        match self.fault_policy {
+            FaultPolicy::Halt => {
+                error!("{err}, halting");
+                self.state = State::Halted;
+                self.last_fault = Some(err);
+            },
+            FaultPolicy::Ignore => {
+                warn!("{err}, skipping it");
+                self.pc = self.pc.wrapping_add(2);
+                self.last_fault = None;
+            },
+            FaultPolicy::Pause => {
+                error!("{err}, pausing");
+                self.state = State::Paused;
+                self.last_fault = Some(err);
+            },
         }
-        // println!("AFTER: {}", self.current_op);
-        self.process_op();
     }
 
-    fn process_op(&mut self) {
-        let op: String = self.current_op.clone(); // convert op to hexadecimal string slice
-        println!("INSTRUCTION: {}", op);
-        let chars: Vec<char> = op.chars().collect();              // collect the slice into a vec of chars
-        
+    fn process_op(&mut self, instruction: Instruction) -> Result<(), Chip8Error> {
+        use Instruction::*;
+
+        trace!("{:03X}: {:04X} {:?}", self.pc, self.current_op, instruction);
+
         let mut inc = true; // determine if you increment the program counter
 
-        match chars[0] {
-            '0' => if chars[2] == 'E' { match chars[3] {
-                '0' => self.frame_buffer.clear(),
-                'E' => self.return_from_subroutine(),
-                _ => {},
-            }}, // only needs to handle 00E0 & 00EE
-            '1' => { // JMP
-                self.pc = self.hex_chars_to_u16(chars[1..].to_vec());
-                println!("JMP to {}", self.pc);
+        match instruction {
+            Sys { nnn } => if self.hybrid {
+                self.call_machine_code(nnn);
                 inc = false;
             },
-            '2' => {
-                self.stack.push(self.pc);
-                self.pc = self.hex_chars_to_u16(chars[1..].to_vec());
+            Clear if self.megachip.enabled => self.megachip.clear(),
+            Clear => self.frame_buffer.clear(),
+            ScrollDown { n } if self.megachip.enabled => self.megachip.scroll(0, n as isize),
+            ScrollDown { n } => self.frame_buffer.scroll_down(n as usize),
+            ScrollRight if self.megachip.enabled => self.megachip.scroll(4, 0),
+            ScrollRight => self.frame_buffer.scroll_right(4),
+            ScrollLeft if self.megachip.enabled => self.megachip.scroll(-4, 0),
+            ScrollLeft => self.frame_buffer.scroll_left(4),
+            ScrollUp { n } if self.megachip.enabled => self.megachip.scroll(0, -(n as isize)),
+            ScrollUp { n } => self.frame_buffer.scroll_up(n as usize),
+            SelectPlanes { n } => self.frame_buffer.select_planes(n),
+            Exit => {
+                self.state = State::Halted;
+                inc = false;
             },
-            '3' => {
-                if self.registers[self.hex_char_to_u16(chars[1]) as usize] as u16 == self.hex_chars_to_u16(chars[2..].to_vec()) {
-                    self.pc += 2; // skip next instruction
-                }
+            Return => self.return_from_subroutine()?,
+            Lores => if !self.platform.fixed_hires() {
+                self.set_resolution(false);
             },
-            '4' => {
-                if self.registers[self.hex_char_to_u16(chars[1]) as usize] as u16 != self.hex_chars_to_u16(chars[2..].to_vec()) {
-                    self.pc += 2; // skip next instruction
-                }
+            Hires => self.set_resolution(true),
+            Jump { nnn } => {
+                self.pc = nnn;
+                inc = false;
+            },
+            Call { nnn } => {
+                self.call_subroutine(nnn)?;
+                inc = false;
+            },
+            SkipEqImm { x, nn } => if self.registers[x as usize] == nn {
+                self.skip_next()?;
+            },
+            SkipNeImm { x, nn } => if self.registers[x as usize] != nn {
+                self.skip_next()?;
             },
-            '5' => if chars[3] == '0' && self.registers[self.hex_char_to_u16(chars[1]) as usize] == self.registers[self.hex_char_to_u16(chars[1]) as usize] {
-                self.pc += 2; // skip next instruction
-            },
-            '6' => {
-                let index = self.hex_char_to_u16(chars[1]);
-                println!("SETTING REGISTER V{:X} to {}", index, self.hex_chars_to_u16(chars[2..].to_vec()));
-                self.registers[index as usize] = self.hex_chars_to_u16(chars[2..].to_vec()) as u8;
-            }, // LDR
-            '7' => {
-                let index = self.hex_char_to_u16(chars[1]);
-                println!("ADDING TO REGISTER V{:X}", index);
-                if (255 - self.registers[index as usize] as u16) >= self.hex_chars_to_u16(chars[2..].to_vec()) {
-                    self.registers[index as usize] += self.hex_chars_to_u16(chars[2..].to_vec()) as u8;
-                } else {
-                    self.registers[index as usize] = 255;
+            StoreRange { x, y } => { // XO-CHIP VX -> VY gets stored at the index register, which doesn't move
+                let range = self.memory_range(self.index_reg, x.abs_diff(y) as usize + 1)?;
+                for (address, register) in range.zip(register_range(x, y)) {
+                    self.memory[address] = self.registers[register];
                 }
             },
-            '8' => { println!("!!!!!!!!!!! CHARS 3: {}", chars[3]); match chars[3] {
-                '0' => { // assignment
-                    self.registers[self.hex_char_to_u16(chars[1]) as usize] = self.registers[self.hex_char_to_u16(chars[2]) as usize];
-                },
-                '1' => { // bitwise or
-                    self.registers[self.hex_char_to_u16(chars[1]) as usize] |= self.registers[self.hex_char_to_u16(chars[2]) as usize];
-                },
-                '2' => { // bitwise and
-                    self.registers[self.hex_char_to_u16(chars[1]) as usize] &= self.registers[self.hex_char_to_u16(chars[2]) as usize];
-                },
-                '3' => { // bitwise xor
-                    self.registers[self.hex_char_to_u16(chars[1]) as usize] ^= self.registers[self.hex_char_to_u16(chars[2]) as usize];
-                },
-                '4' => {
-                    let index = self.hex_char_to_u16(chars[1]);
-                    let second_index = self.hex_char_to_u16(chars[2]);
-                    if 255 - self.registers[index as usize] >= self.registers[second_index as usize] {
-                        self.registers[index as usize] += self.registers[second_index as usize];
-                        self.registers[15] = 0;
-                    } else {
-                        self.registers[index as usize] = 255;
-                        self.registers[15] = 1;
-                    }
-                }, // add (with carry flag)
-                '5' => { // subtract VX - VY into VX
-                    let index = self.hex_char_to_u16(chars[1]);
-                    let second_index = self.hex_char_to_u16(chars[2]);
-                    if self.registers[index as usize] > self.registers[second_index as usize] {
-                        println!("SUBTRACTING V{} - V{}", index, second_index);
-                        println!("{} - {} =", self.registers[index as usize], self.registers[second_index as usize]);
-                        self.registers[index as usize] -= self.registers[second_index as usize];
-                        println!("{}", self.registers[index as usize]);
-                        self.registers[15] = 1;
-                    } else {
-                        self.registers[index as usize] = 0;
-                        self.registers[15] = 0;
-                    }
-                },
-                '6' => { // bitwise right
-                    if SUPER_CHIP {
-                        self.registers[self.hex_char_to_u16(chars[1]) as usize] = self.registers[self.hex_char_to_u16(chars[2]) as usize];
-                    }
-                    if get_bit(&self.registers[self.hex_char_to_u16(chars[1]) as usize], &7) {
-                        self.registers[15] = 1;
-                    } else {
-                        self.registers[15] = 0;
-                    }
-                    self.registers[self.hex_char_to_u16(chars[1]) as usize] = self.registers[self.hex_char_to_u16(chars[1]) as usize] >> 1;
-                },
-                '7' => { // subtract VY - VX into VX
-                    let index = self.hex_char_to_u16(chars[1]);
-                    let second_index = self.hex_char_to_u16(chars[2]);
-
-                    println!("!!!!!!!!!!! EIGHT SEVEN");
-
-                    if self.registers[second_index as usize] > self.registers[index as usize] {
-                        println!("SUBTRACTING V{} - V{}", second_index, index);
-                        println!("{} - {} =", self.registers[second_index as usize], self.registers[index as usize]);
-                        self.registers[index as usize] = self.registers[second_index as usize] - self.registers[index as usize];
-                        println!("{}", self.registers[index as usize]);
-                        self.registers[15] = 1;
-                    } else {
-                        println!("SUBTRACTING V{} - V{}", second_index, index);
-                        self.registers[index as usize] = 0;
-                        self.registers[15] = 0;
-                    }
-                },
-                'E' => { // bitwise left
-                    if SUPER_CHIP {
-                        self.registers[self.hex_char_to_u16(chars[1]) as usize] = self.registers[self.hex_char_to_u16(chars[2]) as usize];
-                    }
-                    if get_bit(&self.registers[self.hex_char_to_u16(chars[1]) as usize], &0) {
-                        self.registers[15] = 1;
-                    } else {
-                        self.registers[15] = 0;
-                    }
-                    self.registers[self.hex_char_to_u16(chars[1]) as usize] = self.registers[self.hex_char_to_u16(chars[1]) as usize] << 1;
-                },
-                _ => {},
-            }},
-            '9' => if chars[3] == '0' && self.registers[self.hex_char_to_u16(chars[1]) as usize] != self.registers[self.hex_char_to_u16(chars[1]) as usize] {
-                self.pc += 2; // skip next instruction
-            },
-            'A' => {
-                self.index_reg = self.hex_chars_to_u16(chars[1..].to_vec());
-                println!("SETTINGS INDEX REGISTER TO {}", self.index_reg);
-            }, // SET INDEX REG
-            'B' => {
-                self.pc = self.hex_chars_to_u16(chars[1..].to_vec());
-                let mut reg = 0;
-                if SUPER_CHIP {
-                    reg = self.hex_char_to_u16(chars[1]);
+            LoadRange { x, y } => { // XO-CHIP memory at the index register gets loaded into VX -> VY
+                let range = self.memory_range(self.index_reg, x.abs_diff(y) as usize + 1)?;
+                for (address, register) in range.zip(register_range(x, y)) {
+                    self.registers[register] = self.memory[address];
                 }
-                self.pc += self.registers[reg as usize] as u16;
+            },
+            SkipEqReg { x, y } => if self.registers[x as usize] == self.registers[y as usize] {
+                self.skip_next()?;
+            },
+            LoadImm { x, nn } => self.registers[x as usize] = nn,
+            AddImm { x, nn } => { // no carry flag
+                self.registers[x as usize] = self.registers[x as usize].wrapping_add(nn);
+            },
+            Move { x, y } => self.registers[x as usize] = self.registers[y as usize],
+            Or { x, y } => {
+                self.registers[x as usize] |= self.registers[y as usize];
+                self.reset_vf();
+            },
+            And { x, y } => {
+                self.registers[x as usize] &= self.registers[y as usize];
+                self.reset_vf();
+            },
+            Xor { x, y } => {
+                self.registers[x as usize] ^= self.registers[y as usize];
+                self.reset_vf();
+            },
+            Add { x, y } => { // add (with carry flag)
+                let (sum, carry) = self.registers[x as usize].overflowing_add(self.registers[y as usize]);
+                self.set_with_flag(x, sum, carry);
+            },
+            Sub { x, y } => { // subtract VX - VY into VX, VF is 1 when there's no borrow
+                let (difference, borrow) = self.registers[x as usize].overflowing_sub(self.registers[y as usize]);
+                self.set_with_flag(x, difference, !borrow);
+            },
+            ShiftRight { x, y } => { // bitwise right, VF is the bit shifted out
+                let value = self.shift_operand(x, y);
+                self.set_with_flag(x, value >> 1, get_bit(&value, &7));
+            },
+            SubReverse { x, y } => { // subtract VY - VX into VX, VF is 1 when there's no borrow
+                let (difference, borrow) = self.registers[y as usize].overflowing_sub(self.registers[x as usize]);
+                self.set_with_flag(x, difference, !borrow);
+            },
+            ShiftLeft { x, y } => { // bitwise left, VF is the bit shifted out
+                let value = self.shift_operand(x, y);
+                self.set_with_flag(x, value << 1, get_bit(&value, &0));
+            },
+            SkipNeReg { x, y } => if self.registers[x as usize] != self.registers[y as usize] {
+                self.skip_next()?;
+            },
+            LoadIndex { nnn } => self.index_reg = nnn as u32,
+            LoadLongIndex => { // XO-CHIP F000 NNNN, the address is the word after the instruction
+                self.index_reg = self.read_operand()? as u32;
+                self.pc += 2;
+            },
+            JumpOffset { x, nnn } => {
+                let reg = if self.quirks.jump_uses_vx { x } else { 0 };
+                self.pc = nnn + self.registers[reg as usize] as u16;
                 inc = false;
             },
-            'C' => {
-                let last_two = self.hex_chars_to_u16(chars[2..].to_vec());
-                self.registers[self.hex_char_to_u16(chars[1]) as usize] = (rand::thread_rng().gen_range(0..last_two) & last_two) as u8;
+            Random { x, nn } => {
+                self.registers[x as usize] = rand::random::<u8>() & nn;
             },
-            'D' => {
-                let x = self.registers[self.hex_char_to_u16(chars[1]) as usize] % 64;
-                let mut y = self.registers[self.hex_char_to_u16(chars[2]) as usize] % 32;
-                let n = self.hex_char_to_u16(chars[3]);
+            Draw { x, y, .. } if self.megachip.enabled => { // MEGA-CHIP sprites are one palette index per byte
+                let x = self.registers[x as usize] as usize;
+                let y = self.registers[y as usize] as usize;
 
-                let mut vf_flip = false;
-                
-                for i in 0..n {
-                    if y > 31 {
-                        break;
-                    }
-                    let location = self.index_reg + i; // 8 bits
-                    vf_flip = self.frame_buffer.set(x,y, self.memory[location as usize]);
-                    y += 1;
+                let len = self.megachip.sprite_width * self.megachip.sprite_height;
+                let sprite = self.read_memory(self.index_reg, len)?;
+
+                let vf_flip = self.megachip.draw_sprite(x, y, &sprite);
+                self.registers[15] = vf_flip as u8;
+            },
+            Draw { .. } if self.quirks.display_wait && !self.frame_buffer.hires() && !self.vblank => {
+                self.waiting_for_vblank = true; // the VIP only drew sprites right after the display interrupt
+                inc = false;
+            },
+            Draw { x, y, n } => { // Fun stuff (drawing)
+                self.vblank = false;
+
+                let x = self.registers[x as usize] as usize;
+                let y = self.registers[y as usize] as usize;
+
+                // DXY0 draws a 16x16 sprite made of 2 bytes per row
+                let (rows, bytes_per_row) = if n == 0 { (16, 2) } else { (n as usize, 1) };
+
+                // XO-CHIP stores the sprite for each selected plane one after the other
+                let len = rows * bytes_per_row * self.frame_buffer.selected_planes();
+                let sprite = self.read_memory(self.index_reg, len)?;
+
+                let vf_flip = self.frame_buffer.draw_sprite(x, y, rows, bytes_per_row, &sprite);
+                self.registers[15] = vf_flip as u8;
+            },
+            SkipKey { x } => if self.keypad.is_held(self.registers[x as usize]) {
+                self.skip_next()?;
+            },
+            SkipNotKey { x } => if !self.keypad.is_held(self.registers[x as usize]) {
+                self.skip_next()?;
+            },
+            LoadDelay { x } => self.registers[x as usize] = self.delay_timer,
+            WaitKey { x } => match self.keypad.wait_for_key() {
+                Some(key) => self.registers[x as usize] = key,
+                None => inc = false, // come back here until a key is pressed and released
+            },
+            SetDelay { x } => self.delay_timer = self.registers[x as usize],
+            SetSound { x } => self.sound_timer = self.registers[x as usize],
+            LoadAudio => { // XO-CHIP audio pattern from the index register
+                let pattern = self.read_memory(self.index_reg, PATTERN_BYTES)?;
+                self.audio.load_pattern(&pattern);
+            },
+            SetPitch { x } => self.audio.set_pitch(self.registers[x as usize]),
+            AddIndex { x } => { // Add to index register (Spacefight 2091! ROM relies on carry flag behaviour that's commented out here)
+                self.index_reg = self.index_reg.wrapping_add(self.registers[x as usize] as u32); // shouldn't need to handle index register overflow
+                // if self.index_reg > 0x0FF { // over 12-bit
+                //     self.registers[15] = 1;
+                // }
+            },
+            LoadFont { x } => { // Font character
+                let character = (self.registers[x as usize] % 16) as u32;
+                self.index_reg = character * 5; // 5 rows or bytes in each letter sprite
+            },
+            LoadLargeFont { x } => { // SUPER-CHIP large font character
+                let character = (self.registers[x as usize] % 16) as u32;
+                self.index_reg = LARGE_FONT_START + character * 10; // 10 rows in each large sprite
+            },
+            StoreBcd { x } => { // Splice register value by the units, tens, hundreds into memory starting at the index register
+                let number = self.registers[x as usize];
+                let digit_three = number % 10;
+                let digit_two = (number % 100 - digit_three) / 10;
+                let digit_one = (number - digit_two*10 - digit_three) / 100;
+
+                let range = self.memory_range(self.index_reg, 3)?;
+                self.memory[range].copy_from_slice(&[digit_one, digit_two, digit_three]);
+            },
+            StoreRegisters { x } => { // V0 -> VX gets loaded with memory starting at index register
+                let range = self.memory_range(self.index_reg, x as usize + 1)?;
+                self.memory[range].copy_from_slice(&self.registers[..=x as usize]);
+
+                if self.quirks.load_store_inc { // older interpreters incremented index registers as they worked
+                    self.index_reg += x as u32 + 1;
                 }
+            },
+            LoadRegisters { x } => { // memory starting at index register gets loaded with V0 -> VX
+                let range = self.memory_range(self.index_reg, x as usize + 1)?;
+                self.registers[..=x as usize].copy_from_slice(&self.memory[range]);
 
-                if vf_flip {
-                    self.registers[15] = 1;
-                } else {
-                    self.registers[15] = 0;
+                if self.quirks.load_store_inc { // older interpreters incremented index registers as they worked
+                    self.index_reg += x as u32 + 1;
                 }
-                
-            }, // Fun stuff (drawing)
-            'E' => if self.key_pressed { match chars[2..3] {
-                ['9', 'E'] => {
-                    if self.registers[self.hex_char_to_u16(chars[1]) as usize] == self.last_key.unwrap() {
-                        self.pc += 2;
-                    }
-                },
-                ['A', '1'] => {
-                    if self.registers[self.hex_char_to_u16(chars[1]) as usize] != self.last_key.unwrap() {
-                        self.pc += 2;
-                    }
-                },
-                _ => {},
-            }},
-            'F' => match chars[2..3] {
-                ['0', '7'] => {
-                    self.registers[self.hex_char_to_u16(chars[1]) as usize] = self.delay_timer;
-                },
+            },
+            CycleBackground => self.chip8x.cycle_background(),
+            AddNibbles { x, y } => {
+                self.registers[x as usize] = chip8x::add_nibbles(self.registers[x as usize], self.registers[y as usize]);
+            },
+            ColourZones { x, y } => { // VX horizontal, V(X+1) vertical, VY colour
+                let vertical = self.registers[(x as usize + 1) % 16];
+                self.chip8x.colour_zones(self.registers[x as usize], vertical, self.registers[y as usize]);
+            },
+            ColourRows { x, y, n } => {
+                let vertical = self.registers[(x as usize + 1) % 16];
+                self.chip8x.colour_rows(self.registers[x as usize], vertical, n, self.registers[y as usize]);
+            },
+            SkipKey2 { x } => if self.chip8x.second_keypad.is_held(self.registers[x as usize]) {
+                self.skip_next()?;
+            },
+            SkipNotKey2 { x } => if !self.chip8x.second_keypad.is_held(self.registers[x as usize]) {
+                self.skip_next()?;
+            },
+            OutputPort { x } => self.port_output = self.registers[x as usize],
+            InputPort { x } => self.registers[x as usize] = self.port_input,
+            Nop => {},
+            WaitDelay => self.waiting_for_delay = true,
+            Skip => self.skip_next()?,
+            SkipGreater { x, y } => if self.registers[x as usize] > self.registers[y as usize] {
+                self.skip_next()?;
+            },
+            StoreRangeInc { x, y } => {
+                let range = self.memory_range(self.index_reg, x.abs_diff(y) as usize + 1)?;
+                self.index_reg = range.end as u32;
+                for (address, register) in range.zip(register_range(x, y)) {
+                    self.memory[address] = self.registers[register];
+                }
+            },
+            LoadRangeInc { x, y } => {
+                let range = self.memory_range(self.index_reg, x.abs_diff(y) as usize + 1)?;
+                self.index_reg = range.end as u32;
+                for (address, register) in range.zip(register_range(x, y)) {
+                    self.registers[register] = self.memory[address];
+                }
+            },
+            JumpBack { nn } => { // relative to the next instruction, like the interpreter's own PC
+                self.pc = self.pc.wrapping_add(2).wrapping_sub(nn as u16);
+                inc = false;
+            },
+            JumpForward { nn } => {
+                self.pc = self.pc.wrapping_add(2).wrapping_add(nn as u16);
+                inc = false;
+            },
+            SkipBytes { x } => self.pc = self.pc.wrapping_add(self.registers[x as usize] as u16),
+            DelayWait { x } => {
+                self.delay_timer = self.registers[x as usize];
+                self.waiting_for_delay = true;
+            },
+            MegaOff => self.megachip.set_enabled(false),
+            MegaOn => self.megachip.set_enabled(true),
+            LoadMegaIndex { nn } => { // MEGA-CHIP 01NN NNNN, the low 16 bits are the word after the instruction
+                self.index_reg = (nn as u32) << 16 | self.read_operand()? as u32;
+                self.pc += 2;
+            },
+            LoadPalette { nn } => {
+                let colours = self.read_memory(self.index_reg, nn as usize * 4)?;
+                self.megachip.load_palette(&colours);
+            },
+            SpriteWidth { nn } => self.megachip.sprite_width = MegaChip::sprite_size(nn),
+            SpriteHeight { nn } => self.megachip.sprite_height = MegaChip::sprite_size(nn),
+            ScreenAlpha { nn } => self.megachip.alpha = nn,
+            PlaySound { n } => { // header at the index register: 16-bit sample rate, 24-bit length, a reserved byte
+                let header = self.read_memory(self.index_reg, 6)?;
+                let sample_rate = u16::from_be_bytes([header[0], header[1]]) as u32;
+                let len = u32::from_be_bytes([0, header[2], header[3], header[4]]) as usize;
+                let samples = self.read_memory(self.index_reg.wrapping_add(6), len)?;
+                self.audio.play_samples(samples, sample_rate, n == 0);
+            },
+            StopSound => self.audio.stop_samples(),
+            SetBlendMode { n } => self.megachip.blend_mode = BlendMode::from_nibble(n),
+            CollisionColour { nn } => self.megachip.collision_colour = nn,
+            StoreFlags { x } => self.rpl_flags.save(&self.registers[..=x as usize]), // V0 -> VX into the RPL user flags
+            LoadFlags { x } => self.rpl_flags.load(&mut self.registers[..=x as usize]), // RPL user flags into V0 -> VX
+        }
 
-                ['1', '5'] => {
-                    self.delay_timer = self.registers[self.hex_char_to_u16(chars[1]) as usize];
-                },
+        if inc {
+            self.pc = self.pc.wrapping_add(2); // increment program counter by 2
+        }
 
-                ['1', '8'] => {
-                    self.sound_timer = self.registers[self.hex_char_to_u16(chars[1]) as usize];
-                },
+        Ok(())
+    }
 
-                ['1', 'E'] => { // Add to index register (Spacefight 2091! ROM relies on carry flag behaviour that's commented out here)
-                    self.index_reg += self.registers[self.hex_char_to_u16(chars[1]) as usize] as u16; // shouldn't need to handle index register overflow
-                    // if self.index_reg > 0x0FF { // over 12-bit
-                    //     self.registers[15] = 1;
-                    // }
-                },
+    /// Run the 1802 subroutine at `address` the way the VIP interpreter did, with the CHIP8 state laid out in memory
+    /// where the subroutine expects it, then pick up whatever it changed.
+    fn call_machine_code(&mut self, address: u16) {
+        let display = VIP_DISPLAY as usize..VIP_DISPLAY as usize + 0x100;
+        let registers = VIP_REGISTERS as usize..VIP_REGISTERS as usize + 16;
+        if self.memory.len() < display.end {
+            warn!("no room for the VIP display buffer, ignoring machine code call to {address:03X}");
+            self.pc = self.pc.wrapping_add(2);
+            return;
+        }
 
-                ['0', 'A'] => {
-                    if self.key_pressed {
-                        self.registers[self.hex_char_to_u16(chars[1]) as usize] = self.last_key.unwrap();
-                    } else {
-                        self.pc -= 2; // decrement program counter to come back here until key is pressed
-                    }
-                },
+        self.memory[registers.clone()].copy_from_slice(&self.registers);
+        self.memory[display.clone()].copy_from_slice(&self.frame_buffer.pack());
+
+        let x = (self.current_op >> 8) & 0xF;
+        let y = (self.current_op >> 4) & 0xF;
+
+        let mut cpu = Cdp1802::new();
+        cpu.x = 2;
+        cpu.p = 3;
+        cpu.r[2] = VIP_STACK;
+        cpu.r[3] = address;
+        cpu.r[5] = self.pc.wrapping_add(2);
+        cpu.r[6] = VIP_REGISTERS + x;
+        cpu.r[7] = VIP_REGISTERS + y;
+        cpu.r[8] = u16::from_be_bytes([self.delay_timer, self.sound_timer]);
+        cpu.r[0xA] = self.index_reg as u16;
+        cpu.r[0xB] = VIP_DISPLAY;
+
+        let mut steps = 0;
+        while cpu.p != 4 && !cpu.idle {
+            if steps == MACHINE_CODE_STEP_LIMIT {
+                warn!("machine code subroutine at {address:03X} didn't return after {steps} instructions");
+                break;
+            }
+            cpu.step(&mut self.memory);
+            steps += 1;
+        }
+        self.cycles += 2 * steps as u64; // most 1802 instructions take 2 machine cycles
 
-                ['2', '9'] => { // Font character
-                    let character = (self.registers[self.hex_char_to_u16(chars[1]) as usize] % 16) as u16;
-                    self.index_reg = character * 5; // 5 rows or bytes in each letter sprite
-                },
+        self.registers.copy_from_slice(&self.memory[registers]);
+        self.frame_buffer.unpack(&self.memory[display]);
+        [self.delay_timer, self.sound_timer] = cpu.r[8].to_be_bytes();
+        self.index_reg = cpu.r[0xA] as u32;
+        self.pc = cpu.r[5];
+    }
 
-                ['3', '3'] => { // Splice register value by the units, tens, hundreds into memory starting at the index register
-                    let number = self.registers[self.hex_char_to_u16(chars[1]) as usize];
-                    let digit_three = number % 10;
-                    let digit_two = (number % 100 - digit_three) / 10;
-                    let digit_one = (number - digit_two*10 - digit_three) / 100; 
+    fn read_opcode(&self, address: u16) -> Result<u16, Chip8Error> {
+        let range = self.memory_range(address as u32, 2)?;
+        Ok(u16::from_be_bytes([self.memory[range.start], self.memory[range.start + 1]]))
+    }
 
-                    self.memory[self.index_reg as usize] = digit_one;
-                    self.memory[(self.index_reg + 1) as usize] = digit_two;
-                    self.memory[(self.index_reg + 2) as usize] = digit_three;
-                },
+    // the word after the instruction, which long instructions take their operand from
+    fn read_operand(&self) -> Result<u16, Chip8Error> {
+        let address = self.next_instruction(2)?;
+        self.read_opcode(address)
+    }
 
-                ['5', '5'] => { // V0 -> VX gets loaded with memory starting at index register
-                    let max = self.hex_char_to_u16(chars[1]);
-                    for i in 0..=max {
-                        self.memory[(self.index_reg + i) as usize] = self.registers[i as usize];
-                    }
+    // the address `offset` bytes past the instruction, past the end of the address space faults instead of wrapping
+    fn next_instruction(&self, offset: u16) -> Result<u16, Chip8Error> {
+        let addr = self.pc as u32 + offset as u32;
+        self.pc.checked_add(offset).ok_or(Chip8Error::MemoryOutOfBounds { addr, pc: self.pc })
+    }
 
-                    if !SUPER_CHIP { // older interpreters incremented index registers as they worked
-                        self.index_reg += self.hex_char_to_u16(chars[1]) + 1;
-                    }
-                },
+    // `len` bytes of memory starting at `address`
+    fn read_memory(&self, address: u32, len: usize) -> Result<Vec<u8>, Chip8Error> {
+        Ok(self.memory[self.memory_range(address, len)?].to_vec())
+    }
 
-                ['6', '5'] => { // memory starting at index register gets loaded with V0 -> VX
-                    let max = self.hex_char_to_u16(chars[1]);
-                    for i in 0..=max {
-                        self.registers[i as usize] = self.memory[(self.index_reg + i) as usize];
-                    }
+    // XO-CHIP's F000 NNNN and MEGA-CHIP's 01NN NNNN are twice as long as any other instruction, so skips have to jump
+    // over all 4 bytes
+    fn skip_next(&mut self) -> Result<(), Chip8Error> {
+        let next = self.read_operand().unwrap_or_default(); // past the end faults once it's fetched
+        let long = match self.platform {
+            Platform::XoChip => next == 0xF000,
+            Platform::MegaChip => next >> 8 == 0x01,
+            _ => false,
+        };
+        self.pc = self.next_instruction(if long { 4 } else { 2 })?;
+        Ok(())
+    }
 
-                    if !SUPER_CHIP { // older interpreters incremented index registers as they worked
-                        self.index_reg += self.hex_char_to_u16(chars[1]) + 1;
-                    }
-                },
-                _ => {},
-            },
-            _ => {},
-        } 
+    // XO-CHIP clears the screen when the resolution changes, SUPER-CHIP 1.1 leaves the pixels alone
+    fn set_resolution(&mut self, hires: bool) {
+        self.frame_buffer.set_hires(hires, self.platform == Platform::XoChip);
+    }
+
+    // VF is written after the result, so when X is F the flag is what's left in it
+    fn set_with_flag(&mut self, x: u8, value: u8, flag: bool) {
+        self.registers[x as usize] = value;
+        self.registers[15] = flag as u8;
+    }
 
-        if inc { 
-            self.pc += 2; // increment program counter by 2
+    // 8XY6 / 8XYE shift VY into VX on the VIP, later interpreters shift VX in place
+    fn shift_operand(&self, x: u8, y: u8) -> u8 {
+        if self.quirks.shift_uses_vx {
+            self.registers[x as usize]
+        } else {
+            self.registers[y as usize]
         }
+    }
 
-        self.key_pressed = false;
+    fn reset_vf(&mut self) {
+        if self.quirks.vf_reset {
+            self.registers[15] = 0;
+        }
+    }
 
-        self.update_timers();
+    fn tick_timers(&mut self) {
+        self.sound_timer = self.sound_timer.saturating_sub(1);
+        self.delay_timer = self.delay_timer.saturating_sub(1);
     }
-    
-    fn update_timers(&mut self) {
-        let delta = (self.last_instant.elapsed().as_secs() * 60) as u8;
-        if 255 - self.sound_timer >= delta {
-            self.sound_timer -= delta;
-        } else {
-            self.sound_timer = 0;
-        }
-        if 255 - self.delay_timer >= delta {
-            self.delay_timer -= delta;
-        } else {
-            self.delay_timer = 0;
+
+    fn call_subroutine(&mut self, address: u16) -> Result<(), Chip8Error> { // CALL
+        let Some(entry) = self.stack.get_mut(self.sp) else {
+            return Err(Chip8Error::StackOverflow { pc: self.pc });
+        };
+        *entry = self.pc;
+        self.sp += 1;
+        self.pc = address;
+        Ok(())
+    }
+
+    fn return_from_subroutine(&mut self) -> Result<(), Chip8Error> { // RET
+        if self.sp == 0 {
+            return Err(Chip8Error::StackUnderflow { pc: self.pc });
         }
-        self.last_instant = Instant::now();
+        self.sp -= 1;
+        self.pc = self.stack[self.sp];
+        Ok(())
     }
 
-    fn hex_chars_to_u16(&self, chars: Vec<char>) -> u16 {
-        let hex_string = chars[0..].iter().collect::<String>();
-        u16::from_str_radix(&hex_string, 16).expect("Couldn't convert hex to u16")
+    /// Resize the stack to hold `depth` return addresses, emptying it
+    fn set_stack_depth(&mut self, depth: usize) {
+        self.stack = vec![0; depth];
+        self.sp = 0;
     }
 
-    fn hex_char_to_u16(&self, c: char) -> u16 {
-        match c {
-            '0' => 0,
-            '1' => 1,
-            '2' => 2,
-            '3' => 3,
-            '4' => 4,
-            '5' => 5,
-            '6' => 6,
-            '7' => 7,
-            '8' => 8,
-            '9' => 9,
-            'A' => 10,
-            'B' => 11,
-            'C' => 12,
-            'D' => 13,
-            'E' => 14,
-            'F' => 15,
-            _ => 0,
+    // `len` bytes of memory at `address`. Every fetch, read and write goes through here, nothing wraps around the end
+    // of memory so running off it faults.
+    fn memory_range(&self, address: u32, len: usize) -> Result<Range<usize>, Chip8Error> {
+        let start = address as usize;
+        if start + len > self.memory.len() {
+            return Err(Chip8Error::MemoryOutOfBounds { addr: (start + len - 1) as u32, pc: self.pc });
         }
+        Ok(start..start + len)
+    }
+
+    /// Render audio samples for an output device, nothing in the window frontend calls this yet
+    fn render_audio(&mut self, samples: &mut [f32], sample_rate: u32) {
+        self.audio.render(samples, sample_rate, self.sound_timer > 0);
     }
 
-    fn return_from_subroutine(&mut self) { // RET
-        self.pc = self.stack[self.stack.len() - 1];
-        self.stack.pop().unwrap();
+    /// Size of the display in the active resolution
+    fn resolution(&self) -> (u32, u32) {
+        if self.megachip.enabled {
+            return (MEGA_WIDTH as u32, MEGA_HEIGHT as u32);
+        }
+        let (width, height) = self.frame_buffer.size();
+        (width as u32, height as u32)
     }
 
     /// Draw the `World` state to the frame buffer.
     ///
     /// Assumes the default texture format: `wgpu::TextureFormat::Rgba8UnormSrgb`
+    /// and a frame of `resolution()` pixels.
     fn draw(&self, frame: &mut [u8]) {
+        if self.megachip.enabled {
+            for (i, pixel) in frame.chunks_exact_mut(4).enumerate() {
+                pixel.copy_from_slice(&self.megachip.rgba(i));
+            }
+            return;
+        }
+
         let frame_buffer = self.frame_buffer.export();
+        let (width, _) = self.frame_buffer.size();
+        let scale = width / self.frame_buffer.width(); // lores pixels can be doubled
         for (i, pixel) in frame.chunks_exact_mut(4).enumerate() {
-            let rgba = if frame_buffer[i] {
-                [0xff, 0xff, 0xff, 0xff]
+            let rgba = if self.platform == Platform::Chip8X {
+                self.chip8x.rgba(i % width / scale, i / width / scale, frame_buffer[i] != 0)
             } else {
-                [0x00, 0x00, 0x00, 0xff]
+                PALETTE[frame_buffer[i] as usize]
             };
 
             pixel.copy_from_slice(&rgba);
@@ -567,15 +877,113 @@ impl CHIP8 {
 }
 
 fn main() -> Result<(), Error> {
-    let rom_location = &std::env::args().collect::<Vec<String>>()[1];
+    // usage: chip8 <rom> [--platform chip8|chip8x|chip8e|chip10|schip|xochip|megachip] [--quirks vip|chip48|schip|xochip] [--hybrid] [--ipf <instructions per frame>] [--timing instructions|vip] [--on-fault ignore|halt|pause] [--stack-depth <n>] [--exit-on-halt]
+    let mut rom_location = None;
+    let mut platform = Platform::default();
+    let mut quirks = None; // defaults to the platform's quirks
+    let mut exit_on_halt = false; // close the window when the program runs 00FD
+    let mut hybrid = false; // run 0NNN machine code on the 1802 core
+    let mut instructions_per_frame = Scheduler::budget_for(INSTRUCTIONS_PER_SECOND);
+    let mut timing = Timing::default();
+    let mut fault_policy = FaultPolicy::default();
+    let mut stack_depth = None; // defaults to the platform's depth
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--platform" => match args.next().unwrap_or_default().parse() {
+                Ok(selected) => platform = selected,
+                Err(err) => {
+                    eprintln!("{err}");
+                    std::process::exit(2);
+                },
+            },
+            "--quirks" => match args.next().unwrap_or_default().parse() {
+                Ok(preset) => quirks = Some(preset),
+                Err(err) => {
+                    eprintln!("{err}");
+                    std::process::exit(2);
+                },
+            },
+            "--hybrid" => hybrid = true,
+            "--ipf" => match args.next().unwrap_or_default().parse() {
+                Ok(budget) => instructions_per_frame = budget,
+                Err(err) => {
+                    eprintln!("--ipf: {err}");
+                    std::process::exit(2);
+                },
+            },
+            "--timing" => match args.next().unwrap_or_default().parse() {
+                Ok(selected) => timing = selected,
+                Err(err) => {
+                    eprintln!("{err}");
+                    std::process::exit(2);
+                },
+            },
+            "--on-fault" => match args.next().unwrap_or_default().parse() {
+                Ok(policy) => fault_policy = policy,
+                Err(err) => {
+                    eprintln!("{err}");
+                    std::process::exit(2);
+                },
+            },
+            "--stack-depth" => match args.next().unwrap_or_default().parse() {
+                Ok(depth) => stack_depth = Some(depth),
+                Err(err) => {
+                    eprintln!("--stack-depth: {err}");
+                    std::process::exit(2);
+                },
+            },
+            "--exit-on-halt" => exit_on_halt = true,
+            _ => rom_location = Some(arg),
+        }
+    }
+
+    let Some(rom_location) = rom_location else {
+        eprintln!("usage: chip8 <rom> [--platform chip8|chip8x|chip8e|chip10|schip|xochip|megachip] [--quirks vip|chip48|schip|xochip] [--hybrid] [--ipf <instructions per frame>] [--timing instructions|vip] [--on-fault ignore|halt|pause] [--stack-depth <n>] [--exit-on-halt]");
+        std::process::exit(2);
+    };
+    let quirks = quirks.unwrap_or(platform.quirks());
     println!("Running CHIP8 ROM '{}'", rom_location);
-    let data: Vec<u8> = fs::read(rom_location).unwrap();
+    let data: Vec<u8> = match fs::read(&rom_location) {
+        Ok(data) => data,
+        Err(err) => {
+            eprintln!("couldn't read '{rom_location}': {err}");
+            std::process::exit(1);
+        },
+    };
+
+    let rom_name = Path::new(&rom_location).file_name().unwrap_or_default().to_string_lossy();
+    let keymap = match Keymap::load(&rom_name) {
+        Ok(keymap) => keymap,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(2);
+        },
+    };
 
     env_logger::init();
+    let rpl_flags = RplFlags::for_rom(&data, platform.rpl_flags());
+    let mut emulator = match CHIP8::new(data, platform, quirks) {
+        Ok(emulator) => emulator,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        },
+    };
+    emulator.rpl_flags = rpl_flags;
+    emulator.hybrid = hybrid;
+    emulator.scheduler.instructions_per_frame = instructions_per_frame;
+    emulator.scheduler.timing = timing;
+    emulator.fault_policy = fault_policy;
+    if let Some(depth) = stack_depth {
+        emulator.set_stack_depth(depth);
+    }
     let event_loop = EventLoop::new().unwrap();
     let mut input = WinitInputHelper::new();
     let window = {
-        let size = LogicalSize::new((WIDTH * K)as f64, (HEIGHT * K) as f64);
+        let (width, height) = emulator.resolution();
+        let size = LogicalSize::new((width * K) as f64, (height * K) as f64);
         WindowBuilder::new()
             .with_title("CHIP8 Emulator")
             .with_inner_size(size)
@@ -584,13 +992,15 @@ fn main() -> Result<(), Error> {
             .unwrap()
     };
 
+    let mut resolution = emulator.resolution();
     let mut pixels = {
         let window_size = window.inner_size();
         let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
-        Pixels::new(WIDTH, HEIGHT, surface_texture)?
+        Pixels::new(resolution.0, resolution.1, surface_texture)?
     };
-    let mut emulator = CHIP8::new(data);
 
+    let mut exited = false;
+    let mut last_stats = Instant::now();
     let res = event_loop.run(|event, elwt| {
         // Draw the current frame
         if let Event::WindowEvent {
@@ -598,6 +1008,16 @@ fn main() -> Result<(), Error> {
             ..
         } = event
         {
+            // follow SUPER-CHIP resolution switches
+            if emulator.resolution() != resolution {
+                resolution = emulator.resolution();
+                if let Err(err) = pixels.resize_buffer(resolution.0, resolution.1) {
+                    log_error("pixels.resize_buffer", err);
+                    elwt.exit();
+                    return;
+                }
+            }
+
             emulator.draw(pixels.frame_mut());
             if let Err(err) = pixels.render() {
                 log_error("pixels.render", err);
@@ -619,37 +1039,29 @@ fn main() -> Result<(), Error> {
                 emulator.pause();
             }
 
-            /*  1 2 3 4 | 1 2 3 C
-             *  Q W E R | 4 5 6 D
-             *  A S D F | 7 8 9 E
-             *  Z X C V | A 0 B F
+            // keypad from the keymap config, QWERTY by default
+            emulator.keypad.set_held(keymap.held(|key| input.key_held(key)));
+
+            /* CHIP-8X second keypad on the number pad
+             *  7 8 9 / | 1 2 3 C
+             *  4 5 6 * | 4 5 6 D
+             *  1 2 3 - | 7 8 9 E
+             *  0 . ⏎ + | A 0 B F
              */
 
-            let keys = vec![Digit1, Digit2, Digit3, Digit4, KeyQ, KeyW, KeyE, KeyR, KeyA, KeyS, KeyD, KeyF, KeyZ, KeyX, KeyC, KeyV];
+            let second_keypad = [
+                (Numpad7, 0x1), (Numpad8, 0x2), (Numpad9, 0x3), (NumpadDivide, 0xC),
+                (Numpad4, 0x4), (Numpad5, 0x5), (Numpad6, 0x6), (NumpadMultiply, 0xD),
+                (Numpad1, 0x7), (Numpad2, 0x8), (Numpad3, 0x9), (NumpadSubtract, 0xE),
+                (Numpad0, 0xA), (NumpadDecimal, 0x0), (NumpadEnter, 0xB), (NumpadAdd, 0xF),
+            ];
 
-            for key in keys {
+            for (key, hex) in second_keypad {
                 if input.key_pressed(key) {
-                    emulator.last_key = Some(match key {
-                        Digit1 => 0x1,
-                        Digit2 => 0x2,
-                        Digit3 => 0x3,
-                        Digit4 => 0xC,
-                        KeyQ   => 0x4,
-                        KeyW   => 0x5,
-                        KeyE   => 0x6,
-                        KeyR   => 0xD,
-                        KeyA   => 0x7,
-                        KeyS   => 0x8,
-                        KeyD   => 0x9,
-                        KeyF   => 0xE,
-                        KeyZ   => 0xA,
-                        KeyX   => 0x0,
-                        KeyC   => 0xB,
-                        KeyV   => 0xF,
-                        _ => 0x0, // literally impossible just pleasing my LSP
-                    });
-                    emulator.key_pressed = true;
-                    break;
+                    emulator.chip8x.second_keypad.press(hex);
+                }
+                if input.key_released(key) {
+                    emulator.chip8x.second_keypad.release(hex);
                 }
             }
 
@@ -662,9 +1074,35 @@ fn main() -> Result<(), Error> {
                 }
             }
 
-            // Update internal state and request a redraw
-            emulator.update();
-            window.request_redraw();
+            // Run the frames that are due, redraw once if any ran and sleep until the next one
+            if emulator.update() > 0 {
+                window.request_redraw();
+            }
+            elwt.set_control_flow(ControlFlow::WaitUntil(Instant::now() + emulator.scheduler.until_next_frame()));
+
+            if last_stats.elapsed() >= Duration::from_secs(1) {
+                last_stats = Instant::now();
+                let stats = emulator.scheduler.stats();
+                debug!(
+                    "{} frames, {} dropped, {:?} average lateness, {:?} worst, {} cycles per frame",
+                    stats.frames, stats.dropped, stats.average_lateness(), stats.worst_lateness,
+                    stats.average_cycles_per_frame(),
+                );
+            }
+
+            if emulator.halted() && !exited {
+                exited = true;
+                let status = match emulator.last_fault {
+                    Some(err) => format!("program faulted: {err}"),
+                    None => String::from("program exited"),
+                };
+                println!("{status}");
+                if exit_on_halt {
+                    elwt.exit();
+                    return;
+                }
+                window.set_title(&format!("CHIP8 Emulator - {status}"));
+            }
         }
     });
     res.map_err(|e| Error::UserDefined(Box::new(e)))
@@ -680,13 +1118,686 @@ fn log_error<E: std::error::Error + 'static>(method_name: &str, err: E) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use scheduler::{ManualClock, FRAME};
+
+    #[test]
+    fn call_and_return() {
+        // 0x200: CALL 0x206, 0x202: JMP 0x202, 0x206: LD V1, 0x42, 0x208: RET
+        let program = vec![0x22, 0x06, 0x12, 0x02, 0x00, 0x00, 0x61, 0x42, 0x00, 0xEE];
+        let mut emu = CHIP8::new(program, Platform::default(), Quirks::default()).unwrap();
+
+        emu.step().unwrap();
+        assert_eq!(emu.pc, 0x206);
+        emu.step().unwrap();
+        assert_eq!(emu.registers[1], 0x42);
+        emu.step().unwrap();
+        assert_eq!(emu.pc, 0x202);
+    }
+
+    #[test]
+    fn shift_quirk() {
+        // 0x200: LD V1, 0x04, 0x202: SHR V0, V1
+        let program = vec![0x61, 0x04, 0x80, 0x16];
+
+        let mut vip = CHIP8::new(program.clone(), Platform::Chip8, Quirks::COSMAC_VIP).unwrap();
+        vip.step().unwrap();
+        vip.step().unwrap();
+        assert_eq!(vip.registers[0], 0x02);
+
+        let mut schip = CHIP8::new(program, Platform::SuperChip, Quirks::SUPER_CHIP).unwrap();
+        schip.step().unwrap();
+        schip.step().unwrap();
+        assert_eq!(schip.registers[0], 0x00);
+    }
+
+    #[test]
+    fn arithmetic_wraps_and_sets_vf_last() {
+        // runs `ops` after loading V0 = a, V1 = b and returns (V0, VF)
+        fn run(a: u8, b: u8, ops: &[u8]) -> (u8, u8) {
+            let mut program = vec![0x60, a, 0x61, b];
+            program.extend(ops);
+            let mut emu = CHIP8::new(program, Platform::Chip8, Quirks::COSMAC_VIP).unwrap();
+            for _ in 0..2 + ops.len() / 2 {
+                emu.step().unwrap();
+            }
+            (emu.registers[0], emu.registers[15])
+        }
+
+        assert_eq!(run(0xFF, 0x02, &[0x70, 0x02]), (0x01, 0)); // ADD V0, 0x02 leaves VF alone
+        assert_eq!(run(0xFF, 0x02, &[0x80, 0x14]), (0x01, 1)); // ADD V0, V1
+        assert_eq!(run(0x10, 0x20, &[0x80, 0x14]), (0x30, 0));
+        assert_eq!(run(0x01, 0x02, &[0x80, 0x15]), (0xFF, 0)); // SUB V0, V1
+        assert_eq!(run(0x02, 0x02, &[0x80, 0x15]), (0x00, 1));
+        assert_eq!(run(0x02, 0x01, &[0x80, 0x17]), (0xFF, 0)); // SUBN V0, V1
+        assert_eq!(run(0x01, 0x01, &[0x80, 0x17]), (0x00, 1));
+        assert_eq!(run(0x00, 0x81, &[0x80, 0x16]), (0x40, 1)); // SHR V0, V1
+        assert_eq!(run(0x00, 0x81, &[0x80, 0x1E]), (0x02, 1)); // SHL V0, V1
+
+        // with X = F the flag overwrites the result: LD VF, a, then the op on VF, V1
+        assert_eq!(run(0xFF, 0x02, &[0x6F, 0xFF, 0x8F, 0x14, 0x80, 0xF0]), (1, 1));
+        assert_eq!(run(0x00, 0x02, &[0x6F, 0x01, 0x8F, 0x15, 0x80, 0xF0]), (0, 0));
+        assert_eq!(run(0x00, 0x02, &[0x6F, 0x00, 0x8F, 0x16, 0x80, 0xF0]), (0, 0));
+        assert_eq!(run(0x00, 0x80, &[0x6F, 0x00, 0x8F, 0x1E, 0x80, 0xF0]), (1, 1));
+    }
+
+    #[test]
+    fn register_comparisons_use_vy() {
+        // 0x200: LD V0, 0x05, 0x202: LD V1, 0x05, 0x204: SE V0, V1, 0x206: (skipped), 0x208: SNE V0, V2, 0x20A: (skipped)
+        let program = vec![0x60, 0x05, 0x61, 0x05, 0x50, 0x10, 0x00, 0x00, 0x90, 0x20];
+        let mut emu = CHIP8::new(program, Platform::Chip8, Quirks::COSMAC_VIP).unwrap();
+        for _ in 0..3 {
+            emu.step().unwrap();
+        }
+        assert_eq!(emu.pc, 0x208);
+        emu.step().unwrap();
+        assert_eq!(emu.pc, 0x20C);
+    }
+
+    #[test]
+    fn key_skips_follow_the_held_keys() {
+        // 0x200: LD V0, 0x0A, 0x202: SKP V0, 0x204: SKNP V0
+        let program = vec![0x60, 0x0A, 0xE0, 0x9E, 0xE0, 0xA1];
+        let mut emu = CHIP8::new(program.clone(), Platform::Chip8, Quirks::COSMAC_VIP).unwrap();
+        emu.keypad.press(0xA);
+        emu.step().unwrap();
+        emu.step().unwrap(); // the key is still held frames after it went down
+        assert_eq!(emu.pc, 0x206);
+
+        let mut emu = CHIP8::new(program, Platform::Chip8, Quirks::COSMAC_VIP).unwrap();
+        emu.keypad.press(0xB);
+        emu.step().unwrap();
+        emu.step().unwrap();
+        assert_eq!(emu.pc, 0x204);
+        emu.step().unwrap();
+        assert_eq!(emu.pc, 0x208);
+    }
+
+    #[test]
+    fn wait_key_blocks_until_release() {
+        // 0x200: LD V3, K
+        let mut emu = CHIP8::new(vec![0xF3, 0x0A], Platform::Chip8, Quirks::COSMAC_VIP).unwrap();
+        emu.step().unwrap();
+        emu.keypad.press(0x7);
+        emu.step().unwrap();
+        assert_eq!(emu.pc, 0x200);
+
+        emu.keypad.release(0x7);
+        emu.step().unwrap();
+        assert_eq!(emu.pc, 0x202);
+        assert_eq!(emu.registers[3], 0x7);
+    }
+
+    #[test]
+    fn timers_tick_once_per_frame() {
+        // 0x200: LD V0, 0x05, 0x202: LD DT, V0, 0x204: JMP 0x204
+        let mut emu = CHIP8::new(vec![0x60, 0x05, 0xF0, 0x15, 0x12, 0x04], Platform::Chip8, Quirks::COSMAC_VIP).unwrap();
+        let clock = ManualClock::default();
+        emu.scheduler = Scheduler::new(Box::new(clock.clone()), 10);
+
+        emu.update();
+        assert_eq!(emu.pc, 0x200);
+
+        clock.advance(FRAME);
+        emu.update();
+        assert_eq!(emu.delay_timer, 4);
+
+        clock.advance(FRAME * 3);
+        emu.update();
+        assert_eq!(emu.delay_timer, 1);
+
+        // nothing runs or ticks while paused
+        emu.pause();
+        clock.advance(FRAME);
+        emu.update();
+        assert_eq!(emu.delay_timer, 1);
+    }
+
+    #[test]
+    fn update_runs_each_due_frame_once() {
+        // 0x200: ADD V0, 0x01, 0x202: JMP 0x200
+        let mut emu = CHIP8::new(vec![0x70, 0x01, 0x12, 0x00], Platform::Chip8, Quirks::COSMAC_VIP).unwrap();
+        let clock = ManualClock::default();
+        emu.scheduler = Scheduler::new(Box::new(clock.clone()), 4);
+
+        clock.advance(FRAME * 3);
+        assert_eq!(emu.update(), 3);
+        assert_eq!(emu.registers[0], 6);
+        assert_eq!(emu.update(), 0);
+        assert_eq!(emu.scheduler.until_next_frame(), FRAME);
+    }
+
+    #[test]
+    fn frames_run_the_instruction_budget() {
+        // 0x200: ADD V0, 0x01, 0x202: JMP 0x200
+        let mut emu = CHIP8::new(vec![0x70, 0x01, 0x12, 0x00], Platform::Chip8, Quirks::COSMAC_VIP).unwrap();
+        emu.scheduler.instructions_per_frame = 8;
+        emu.run_frame();
+        assert_eq!(emu.registers[0], 4);
+    }
+
+    #[test]
+    fn vip_timing_fills_the_frame_with_cycles() {
+        // 0x200: LD V0, 0x01, 0x202: JMP 0x200
+        let mut emu = CHIP8::new(vec![0x60, 0x01, 0x12, 0x00], Platform::Chip8, Quirks::COSMAC_VIP).unwrap();
+        emu.scheduler.timing = Timing::CosmacVip;
+        emu.run_frame();
+
+        let load = vip_cycles(&Instruction::LoadImm { x: 0, nn: 1 }, &emu.registers) as u64;
+        let jump = vip_cycles(&Instruction::Jump { nnn: 0x200 }, &emu.registers) as u64;
+        let frame = emu.cycles;
+        assert!(frame >= VIP_INTERPRETER_CYCLES as u64);
+        assert!(frame < VIP_INTERPRETER_CYCLES as u64 + load.max(jump));
+        assert_eq!(emu.scheduler.stats().cycles_per_frame as u64, frame);
+
+        // the overrun comes out of the next frame
+        emu.run_frame();
+        assert!(emu.cycles < 2 * VIP_INTERPRETER_CYCLES as u64 + load.max(jump));
+    }
+
+    #[test]
+    fn display_wait_draws_one_sprite_per_frame() {
+        // 0x200: DRW V0, V1, 5, 0x202: ADD V2, 0x01, 0x204: JMP 0x200
+        let program = vec![0xD0, 0x15, 0x72, 0x01, 0x12, 0x00];
+
+        let mut vip = CHIP8::new(program.clone(), Platform::Chip8, Quirks::COSMAC_VIP).unwrap();
+        vip.scheduler.instructions_per_frame = 100;
+        for _ in 0..3 {
+            vip.run_frame();
+        }
+        assert_eq!(vip.registers[2], 3);
+        assert_eq!(vip.pc, 0x200);
+
+        let mut schip = CHIP8::new(program, Platform::SuperChip, Quirks::SUPER_CHIP).unwrap();
+        schip.scheduler.instructions_per_frame = 100;
+        schip.run_frame();
+        assert_eq!(schip.registers[2], 33);
+    }
+
+    #[test]
+    fn stalled_draws_are_charged_once() {
+        // 0x200: DRW V0, V1, 5, 0x202: ADD V2, 0x01, 0x204: JMP 0x200
+        let mut emu = CHIP8::new(vec![0xD0, 0x15, 0x72, 0x01, 0x12, 0x00], Platform::Chip8, Quirks::COSMAC_VIP).unwrap();
+        emu.scheduler.timing = Timing::CosmacVip;
+
+        let draw = vip_cycles(&Instruction::Draw { x: 0, y: 1, n: 5 }, &emu.registers);
+        let add = vip_cycles(&Instruction::AddImm { x: 2, nn: 1 }, &emu.registers);
+        let jump = vip_cycles(&Instruction::Jump { nnn: 0x200 }, &emu.registers);
+        for _ in 0..3 { // every frame draws once and stalls on the next draw
+            emu.run_frame();
+            assert_eq!(emu.scheduler.stats().cycles_per_frame, draw + add + jump);
+        }
+        assert_eq!(emu.cycles, 3 * (draw + add + jump) as u64);
+    }
+
+    #[test]
+    fn stepped_draws_wait_for_vblank() {
+        // 0x200: DRW V0, V1, 5, 0x202: DRW V0, V1, 5
+        let mut emu = CHIP8::new(vec![0xD0, 0x15, 0xD0, 0x15], Platform::Chip8, Quirks::COSMAC_VIP).unwrap();
+        emu.step().unwrap();
+        emu.step().unwrap();
+        assert_eq!(emu.pc, 0x202);
+        assert!(emu.waiting_for_vblank);
+
+        emu.vblank();
+        emu.step().unwrap();
+        assert_eq!(emu.pc, 0x204);
+    }
+
+    #[test]
+    fn clipping_follows_the_quirks() {
+        // 0x200: LD V0, 0x3C, 0x202: LD I, 0x208, 0x204: DRW V0, V1, 1, 0x208: sprite
+        let program = vec![0x60, 0x3C, 0xA2, 0x08, 0xD0, 0x11, 0x00, 0x00, 0xFF];
+
+        let mut vip = CHIP8::new(program.clone(), Platform::Chip8, Quirks::COSMAC_VIP).unwrap();
+        let mut xo = CHIP8::new(program, Platform::XoChip, Quirks::XO_CHIP).unwrap();
+        for _ in 0..3 {
+            vip.step().unwrap();
+            xo.step().unwrap();
+        }
+        assert_eq!(vip.frame_buffer.get(0, 0), 0);
+        assert_eq!(xo.frame_buffer.get(3, 0), 1);
+    }
+
+    #[test]
+    fn oversized_roms_are_rejected() {
+        let result = CHIP8::new(vec![0; 0x1000], Platform::Chip8, Quirks::COSMAC_VIP);
+        assert_eq!(result.err(), Some(Chip8Error::RomTooLarge { size: 0x1000, max: 0xE00 }));
+        assert!(CHIP8::new(vec![0; 0xE00], Platform::Chip8, Quirks::COSMAC_VIP).is_ok());
+    }
+
+    #[test]
+    fn faults_leave_the_instruction_unexecuted() {
+        // 0x200: RET
+        let mut emu = CHIP8::new(vec![0x00, 0xEE], Platform::Chip8, Quirks::COSMAC_VIP).unwrap();
+        assert_eq!(emu.step(), Err(Chip8Error::StackUnderflow { pc: 0x200 }));
+        assert_eq!(emu.pc, 0x200);
+
+        // 0x200: LD I, 0xFFE, 0x202: LD [I], V2
+        let mut emu = CHIP8::new(vec![0xAF, 0xFE, 0xF2, 0x55], Platform::Chip8, Quirks::COSMAC_VIP).unwrap();
+        emu.step().unwrap();
+        assert_eq!(emu.step(), Err(Chip8Error::MemoryOutOfBounds { addr: 0x1000, pc: 0x202 }));
+        assert_eq!(emu.index_reg, 0xFFE);
+
+        // 0x200: (unknown), 0x202: RND V0, 0x00
+        let mut emu = CHIP8::new(vec![0x51, 0x24, 0xC0, 0x00], Platform::Chip8, Quirks::COSMAC_VIP).unwrap();
+        assert_eq!(emu.step(), Err(Chip8Error::UnknownOpcode { opcode: 0x5124, pc: 0x200 }));
+        emu.pc = 0x202;
+        emu.step().unwrap();
+        assert_eq!(emu.registers[0], 0);
+    }
+
+    #[test]
+    fn memory_accesses_past_the_end_fault() {
+        // 0x200: LD I, 0xFFE, 0x202: DRW V0, V1, 3
+        let mut emu = CHIP8::new(vec![0xAF, 0xFE, 0xD0, 0x13], Platform::Chip8, Quirks::COSMAC_VIP).unwrap();
+        emu.step().unwrap();
+        assert_eq!(emu.step(), Err(Chip8Error::MemoryOutOfBounds { addr: 0x1000, pc: 0x202 }));
+
+        // 0x200: LD I, long 0xFFFF, 0x204: SAVE V0 - V1
+        let mut emu = CHIP8::new(vec![0xF0, 0x00, 0xFF, 0xFF, 0x50, 0x12], Platform::XoChip, Quirks::XO_CHIP).unwrap();
+        emu.step().unwrap();
+        assert_eq!(emu.step(), Err(Chip8Error::MemoryOutOfBounds { addr: 0x10000, pc: 0x204 }));
+
+        // operands and skips past the end of the address space don't wrap around to 0
+        let mut emu = CHIP8::new(vec![], Platform::XoChip, Quirks::XO_CHIP).unwrap();
+        emu.memory[0xFFFE..].copy_from_slice(&[0xF0, 0x00]);
+        emu.pc = 0xFFFE;
+        assert_eq!(emu.step(), Err(Chip8Error::MemoryOutOfBounds { addr: 0x10000, pc: 0xFFFE }));
+        emu.memory[0xFFFE..].copy_from_slice(&[0x30, 0x00]); // SE V0, 0x00
+        assert_eq!(emu.step(), Err(Chip8Error::MemoryOutOfBounds { addr: 0x10000, pc: 0xFFFE }));
+        assert_eq!(emu.pc, 0xFFFE);
+
+        // the program counter runs off the end
+        let mut emu = CHIP8::new(vec![], Platform::Chip8, Quirks::COSMAC_VIP).unwrap();
+        emu.pc = 0xFFF;
+        assert_eq!(emu.step(), Err(Chip8Error::MemoryOutOfBounds { addr: 0x1000, pc: 0xFFF }));
+    }
+
+    #[test]
+    fn fault_policies() {
+        // 0x200: (unknown), 0x202: LD V0, 0x01
+        let program = vec![0xE0, 0xFF, 0x60, 0x01];
+        let mut halt = CHIP8::new(program.clone(), Platform::Chip8, Quirks::COSMAC_VIP).unwrap();
+        halt.fault_policy = FaultPolicy::Halt;
+        halt.run_frame();
+        assert!(halt.halted());
+        assert_eq!(halt.last_fault, Some(Chip8Error::UnknownOpcode { opcode: 0xE0FF, pc: 0x200 }));
+
+        let mut ignore = CHIP8::new(program.clone(), Platform::Chip8, Quirks::COSMAC_VIP).unwrap();
+        assert_eq!(ignore.fault_policy, FaultPolicy::Ignore);
+        ignore.scheduler.instructions_per_frame = 2;
+        ignore.run_frame();
+        assert_eq!(ignore.registers[0], 1);
+
+        let mut pause = CHIP8::new(program, Platform::Chip8, Quirks::COSMAC_VIP).unwrap();
+        pause.fault_policy = FaultPolicy::Pause;
+        pause.run_frame();
+        assert_eq!((pause.state, pause.pc), (State::Paused, 0x200));
+    }
+
+    #[test]
+    fn exits_after_a_fault_are_not_reported_as_faults() {
+        // 0x200: RET (nothing to return to), 0x202: EXIT
+        let program = vec![0x00, 0xEE, 0x00, 0xFD];
+
+        let mut ignore = CHIP8::new(program.clone(), Platform::SuperChip, Quirks::SUPER_CHIP).unwrap();
+        ignore.fault_policy = FaultPolicy::Ignore;
+        ignore.run_frame();
+        assert!(ignore.halted());
+        assert_eq!(ignore.last_fault, None);
+
+        let mut pause = CHIP8::new(program, Platform::SuperChip, Quirks::SUPER_CHIP).unwrap();
+        pause.fault_policy = FaultPolicy::Pause;
+        pause.run_frame();
+        assert_eq!(pause.last_fault, Some(Chip8Error::StackUnderflow { pc: 0x200 }));
+
+        // step over the RET by hand and resume
+        pause.pc = 0x202;
+        pause.pause();
+        pause.run_frame();
+        assert!(pause.halted());
+        assert_eq!(pause.last_fault, None);
+    }
+
+    #[test]
+    fn stack_depth_follows_the_platform() {
+        // 0x200: CALL 0x200
+        let mut vip = CHIP8::new(vec![0x22, 0x00], Platform::Chip8, Quirks::COSMAC_VIP).unwrap();
+        for _ in 0..12 {
+            vip.step().unwrap();
+        }
+        assert_eq!(vip.step(), Err(Chip8Error::StackOverflow { pc: 0x200 }));
+        assert_eq!(vip.sp, 12);
+
+        let mut schip = CHIP8::new(vec![0x22, 0x00], Platform::SuperChip, Quirks::SUPER_CHIP).unwrap();
+        for _ in 0..16 {
+            schip.step().unwrap();
+        }
+        assert!(schip.step().is_err());
+
+        schip.set_stack_depth(2);
+        schip.step().unwrap();
+        schip.step().unwrap();
+        assert_eq!(schip.step(), Err(Chip8Error::StackOverflow { pc: 0x200 }));
+
+        // deeper than a 16 bit stack pointer could count
+        schip.set_stack_depth(0x10001);
+        for _ in 0..0x10001 {
+            schip.step().unwrap();
+        }
+        assert_eq!(schip.sp, 0x10001);
+        assert!(schip.step().is_err());
+    }
+
+    #[test]
+    fn large_font_character() {
+        // 0x200: LD V3, 0x0B, 0x202: LD HF, V3
+        let mut emu = CHIP8::new(vec![0x63, 0x0B, 0xF3, 0x30], Platform::default(), Quirks::default()).unwrap();
+        emu.step().unwrap();
+        emu.step().unwrap();
+
+        let start = emu.index_reg as usize;
+        assert_eq!(emu.memory[start..start + 10], get_large_character_sprite('B'));
+    }
+
+    #[test]
+    fn superchip_scrolls_lores_by_hires_pixels() {
+        // 0x200: LD F, V0, 0x202: DRW V0, V0, 5, 0x204: SCD 1
+        let program = vec![0xF0, 0x29, 0xD0, 0x05, 0x00, 0xC1];
+        let mut emu = CHIP8::new(program, Platform::SuperChip, Quirks::SUPER_CHIP).unwrap();
+        for _ in 0..3 {
+            emu.step().unwrap();
+        }
+        assert_eq!(emu.resolution(), (128, 64));
+        assert_eq!(emu.frame_buffer.get(0, 0), 0);
+        assert_eq!(emu.frame_buffer.get(0, 1), 1);
+        assert_eq!(emu.frame_buffer.get(7, 2), 1);
+    }
+
+    #[test]
+    fn only_xochip_clears_on_resolution_changes() {
+        // 0x200: LD F, V0, 0x202: DRW V0, V0, 5, 0x204: HIGH
+        let program = vec![0xF0, 0x29, 0xD0, 0x05, 0x00, 0xFF];
+
+        let mut schip = CHIP8::new(program.clone(), Platform::SuperChip, Quirks::SUPER_CHIP).unwrap();
+        let mut xo = CHIP8::new(program, Platform::XoChip, Quirks::XO_CHIP).unwrap();
+        for _ in 0..3 {
+            schip.step().unwrap();
+            xo.step().unwrap();
+        }
+        assert_eq!(schip.resolution(), (128, 64));
+        assert_eq!(schip.frame_buffer.get(0, 0), 1);
+        assert_eq!(xo.frame_buffer.get(0, 0), 0);
+    }
+
+    #[test]
+    fn exit_halts_the_program() {
+        // 0x200: EXIT, 0x202: LD V0, 0x01
+        let mut emu = CHIP8::new(vec![0x00, 0xFD, 0x60, 0x01], Platform::default(), Quirks::default()).unwrap();
+        emu.step().unwrap();
+        assert!(emu.halted());
+
+        emu.pause();
+        emu.step().unwrap();
+        assert!(emu.halted());
+        assert_eq!(emu.pc, 0x200);
+        assert_eq!(emu.registers[0], 0);
+    }
+
+    #[test]
+    fn long_index_load_and_skip() {
+        // 0x200: SE V0, 0x00, 0x202: LD I, 0x1234 (4 bytes), 0x206: LD I, long 0xBEEF
+        let program = vec![0x30, 0x00, 0xF0, 0x00, 0x12, 0x34, 0xF0, 0x00, 0xBE, 0xEF];
+        let mut emu = CHIP8::new(program, Platform::XoChip, Quirks::XO_CHIP).unwrap();
+        emu.step().unwrap();
+        assert_eq!(emu.pc, 0x206);
+        emu.step().unwrap();
+        assert_eq!(emu.pc, 0x20A);
+        assert_eq!(emu.index_reg, 0xBEEF);
+        assert_eq!(emu.memory.len(), 0x10000);
+    }
+
+    #[test]
+    fn xochip_opcodes_are_unknown_elsewhere() {
+        // 0x200: SE V0, 0x00, 0x202: (F000, only one word long here), 0x204: (5122)
+        let program = vec![0x30, 0x00, 0xF0, 0x00, 0x51, 0x22];
+        let mut emu = CHIP8::new(program, Platform::Chip8, Quirks::COSMAC_VIP).unwrap();
+        emu.step().unwrap();
+        assert_eq!(emu.pc, 0x204);
+
+        emu.pc = 0x202;
+        assert_eq!(emu.step(), Err(Chip8Error::UnknownOpcode { opcode: 0xF000, pc: 0x202 }));
+        emu.pc = 0x204;
+        assert_eq!(emu.step(), Err(Chip8Error::UnknownOpcode { opcode: 0x5122, pc: 0x204 }));
+    }
+
+    #[test]
+    fn register_range_store_and_load() {
+        // 0x200: LD V1, 0x11, 0x202: LD V2, 0x22, 0x204: LD I, 0x300, 0x206: SAVE V2 - V1, 0x208: LOAD V3 - V4
+        let program = vec![0x61, 0x11, 0x62, 0x22, 0xA3, 0x00, 0x52, 0x12, 0x53, 0x43];
+        let mut emu = CHIP8::new(program, Platform::XoChip, Quirks::XO_CHIP).unwrap();
+        for _ in 0..5 {
+            emu.step().unwrap();
+        }
+        assert_eq!(emu.memory[0x300..0x302], [0x22, 0x11]);
+        assert_eq!(emu.registers[3..5], [0x22, 0x11]);
+        assert_eq!(emu.index_reg, 0x300);
+    }
+
+    #[test]
+    fn audio_plays_while_the_sound_timer_runs() {
+        // 0x200: LD I, 0x20A, 0x202: AUDIO, 0x204: LD V0, 0x02, 0x206: LD ST, V0, 0x20A: pattern of all 1s
+        let mut program = vec![0xA2, 0x0A, 0xF0, 0x02, 0x60, 0x02, 0xF0, 0x18, 0x00, 0x00];
+        program.extend([0xFF; PATTERN_BYTES]);
+        let mut emu = CHIP8::new(program, Platform::XoChip, Quirks::XO_CHIP).unwrap();
+
+        let mut samples = [0.0; 8];
+        emu.render_audio(&mut samples, 44100);
+        assert!(samples.iter().all(|&sample| sample == 0.0));
+
+        for _ in 0..4 {
+            emu.step().unwrap();
+        }
+        emu.render_audio(&mut samples, 44100);
+        assert!(samples.iter().all(|&sample| sample > 0.0));
+    }
+
+    #[test]
+    fn hybrid_machine_code_subroutine() {
+        // 0x200: SYS 0x204, 0x202: JMP 0x202, 0x204: LDI 0x42, STR R6 (V2), GLO RA, PLO R5 (jump to 0x2<I>), SEP R4
+        let program = vec![0x02, 0x04, 0x12, 0x02, 0xF8, 0x42, 0x56, 0x8A, 0xA5, 0xD4];
+        let mut emu = CHIP8::new(program, Platform::Chip8, Quirks::COSMAC_VIP).unwrap();
+        emu.hybrid = true;
+        emu.index_reg = 0x10;
+
+        emu.step().unwrap();
+        assert_eq!(emu.registers[2], 0x42);
+        assert_eq!(emu.pc, 0x210);
+    }
+
+    #[test]
+    fn hybrid_calls_machine_code_where_superchip_has_instructions() {
+        // 0x200: SYS 0x0C0, 0x202: SYS 0x0FD, 0x204: JMP 0x204
+        let program = vec![0x00, 0xC0, 0x00, 0xFD, 0x12, 0x04];
+        let mut emu = CHIP8::new(program, Platform::Chip8, Quirks::COSMAC_VIP).unwrap();
+        emu.hybrid = true;
+        emu.memory[0x0C0..0x0C4].copy_from_slice(&[0xF8, 0x11, 0x56, 0xD4]); // LDI 0x11, STR R6 (V0), SEP R4
+        emu.memory[0x0FD..0x101].copy_from_slice(&[0xF8, 0x22, 0x57, 0xD4]); // LDI 0x22, STR R7 (VF), SEP R4
+
+        emu.step().unwrap();
+        assert_eq!((emu.registers[0], emu.pc), (0x11, 0x202));
+        emu.step().unwrap();
+        assert_eq!((emu.registers[15], emu.pc), (0x22, 0x204));
+        assert!(!emu.halted());
+    }
+
+    #[test]
+    fn chip8x_programs_start_at_0x300() {
+        // 0x300: LD V0, 0x21, 0x302: LD V1, 0x17, 0x304: ADD nibbles V0, V1, 0x306: SKP2 V1
+        let program = vec![0x60, 0x21, 0x61, 0x17, 0x50, 0x11, 0xE1, 0xF2];
+        let mut emu = CHIP8::new(program, Platform::Chip8X, Quirks::COSMAC_VIP).unwrap();
+        emu.chip8x.second_keypad.press(0x7);
+        for _ in 0..4 {
+            emu.step().unwrap();
+        }
+        assert_eq!(emu.registers[0], 0x30);
+        assert_eq!(emu.pc, 0x30A);
+    }
+
+    #[test]
+    fn chip8x_colours_take_the_position_from_vx_and_vx_plus_one() {
+        // 0x300: LD V2, 0x01, 0x302: LD V3, 0x01, 0x304: LD V5, 0x04, 0x306: COLOUR zones V2, V5
+        // 0x308: LD V2, 0x10, 0x30A: LD V3, 0x14, 0x30C: LD V5, 0x07, 0x30E: COLOUR rows V2, V5, 1
+        let program = vec![
+            0x62, 0x01, 0x63, 0x01, 0x65, 0x04, 0xB2, 0x50,
+            0x62, 0x10, 0x63, 0x14, 0x65, 0x07, 0xB2, 0x51,
+        ];
+        let mut emu = CHIP8::new(program, Platform::Chip8X, Quirks::COSMAC_VIP).unwrap();
+        for _ in 0..4 {
+            emu.step().unwrap();
+        }
+        assert_eq!(emu.chip8x.rgba(8, 4, true), chip8x::FOREGROUND_COLOURS[4]);
+        assert_eq!(emu.chip8x.rgba(8, 3, true), chip8x::FOREGROUND_COLOURS[1]);
+
+        for _ in 0..4 {
+            emu.step().unwrap();
+        }
+        assert_eq!(emu.chip8x.rgba(16, 20, true), chip8x::FOREGROUND_COLOURS[7]);
+        assert_eq!(emu.chip8x.rgba(16, 21, true), chip8x::FOREGROUND_COLOURS[1]);
+    }
+
+    #[test]
+    fn chip8e_relative_jumps_and_range_store() {
+        // 0x200: JMP forward 2, 0x202: (skipped), 0x204: LD I, 0x300, 0x206: SAVE V0 - V2, 0x208: JMP back 0x0A
+        let program = vec![0xBF, 0x02, 0x00, 0x00, 0xA3, 0x00, 0x50, 0x22, 0xBB, 0x0A];
+        let mut emu = CHIP8::new(program, Platform::Chip8E, Quirks::COSMAC_VIP).unwrap();
+        emu.step().unwrap();
+        assert_eq!(emu.pc, 0x204);
+        emu.step().unwrap();
+        emu.step().unwrap();
+        assert_eq!(emu.index_reg, 0x303);
+        emu.step().unwrap();
+        assert_eq!(emu.pc, 0x200);
+    }
+
+    #[test]
+    fn chip10_is_always_hires() {
+        // 0x200: SYS 0x0FE, CHIP-10 has no LORES
+        let mut emu = CHIP8::new(vec![0x00, 0xFE], Platform::Chip10, Quirks::COSMAC_VIP).unwrap();
+        emu.step().unwrap();
+        assert_eq!(emu.resolution(), (128, 64));
+    }
 
     #[test]
-    fn hex_conversion() {
-        let data: Vec<u8> = fs::read("IBM logo.ch8").unwrap();
-        let emu = CHIP8::new(data);
+    fn megachip_colour_sprites() {
+        // 0x200: MEGA ON, 0x202: LD I, long 0x000210, 0x206: SPRW 1, 0x208: SPRH 1, 0x20A: DRW V0, V1, 0x20C: JMP 0x20C
+        let mut program = vec![0x00, 0x11, 0x01, 0x00, 0x02, 0x10, 0x03, 0x01, 0x04, 0x01, 0xD0, 0x10, 0x12, 0x0C];
+        program.extend([0x00, 0x00, 0x01]); // 0x210: one pixel of palette index 1
+        let mut emu = CHIP8::new(program, Platform::MegaChip, Quirks::SUPER_CHIP).unwrap();
+        emu.step().unwrap();
+        assert_eq!(emu.resolution(), (256, 192));
+        emu.step().unwrap();
+        assert_eq!((emu.index_reg, emu.pc), (0x210, 0x206));
+        for _ in 0..3 {
+            emu.step().unwrap();
+        }
+
+        let mut frame = vec![0; 256 * 192 * 4];
+        emu.draw(&mut frame);
+        assert_eq!(frame[..8], [0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0xff]);
+    }
+
+    // a ROM from tests/roms, they leave their results at 0xE00 and park on a jump to themselves when they're done
+    fn load_test_rom(name: &str, platform: Platform, quirks: Quirks) -> CHIP8 {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("roms").join(name);
+        let mut emu = CHIP8::new(fs::read(path).unwrap(), platform, quirks).unwrap();
+        emu.scheduler.instructions_per_frame = 100;
+        emu.fault_policy = FaultPolicy::Halt;
+        emu
+    }
+
+    fn parked(emu: &CHIP8) -> bool {
+        emu.read_opcode(emu.pc) == Ok(0x1000 | emu.pc)
+    }
 
-        assert_eq!(emu.hex_chars_to_u16(vec!['2','2','8']), 552);
+    fn run_test_rom(name: &str, platform: Platform, quirks: Quirks) -> CHIP8 {
+        let mut emu = load_test_rom(name, platform, quirks);
+        for _ in 0..60 {
+            emu.run_frame();
+            if parked(&emu) {
+                return emu;
+            }
+        }
+        panic!("{name} didn't finish, stopped at {:03X} ({:?})", emu.pc, emu.last_fault);
+    }
+
+    #[test]
+    fn flags_rom() {
+        let expected = [
+            0x30, 0, 0x01, 1, // 8XY4
+            0x03, 1, 0xFD, 0, // 8XY5
+            0x03, 1, 0xFD, 0, // 8XY7
+            0x02, 1, 0x02, 1, // 8XY6, 8XYE
+            0x01, 1, 0x00, 0, 0x01, 1, // VF as VX
+            0x30, 0, 0x10, 1, // VF as VY
+        ];
+        for (platform, quirks) in [
+            (Platform::Chip8, Quirks::COSMAC_VIP),
+            (Platform::SuperChip, Quirks::SUPER_CHIP),
+            (Platform::XoChip, Quirks::XO_CHIP),
+        ] {
+            let emu = run_test_rom("flags.ch8", platform, quirks);
+            assert_eq!(emu.memory[0xE00..0xE1A], expected, "{platform:?}");
+        }
+    }
+
+    #[test]
+    fn quirks_rom() {
+        // VF reset, shift, jump, wrap, display wait, FX55 moving I
+        let vip = run_test_rom("quirks.ch8", Platform::Chip8, Quirks::COSMAC_VIP);
+        assert_eq!(vip.memory[0xE00..0xE07], [0, 2, 1, 0, 1, 0x11, 0x22]);
+
+        let schip = run_test_rom("quirks.ch8", Platform::SuperChip, Quirks::SUPER_CHIP);
+        assert_eq!(schip.memory[0xE00..0xE07], [5, 0, 2, 0, 0, 0x22, 0x00]);
+
+        let xo = run_test_rom("quirks.ch8", Platform::XoChip, Quirks::XO_CHIP);
+        assert_eq!(xo.memory[0xE00..0xE07], [5, 2, 1, 1, 0, 0x11, 0x22]);
+    }
+
+    #[test]
+    fn opcodes_rom() {
+        for (platform, quirks) in [
+            (Platform::Chip8, Quirks::COSMAC_VIP),
+            (Platform::SuperChip, Quirks::SUPER_CHIP),
+            (Platform::XoChip, Quirks::XO_CHIP),
+        ] {
+            let emu = run_test_rom("opcodes.ch8", platform, quirks);
+            assert_eq!(emu.memory[0xE00..0xE0D], [1; 13], "{platform:?}");
+        }
+    }
+
+    #[test]
+    fn keypad_rom() {
+        let mut emu = load_test_rom("keypad.ch8", Platform::Chip8, Quirks::COSMAC_VIP);
+        emu.run_frame();
+        emu.keypad.press(0x5);
+        emu.run_frame();
+        assert_eq!(emu.pc, 0x200); // FX0A waits for the release as well
+        emu.keypad.release(0x5);
+        emu.run_frame();
+        assert_eq!(emu.registers[0], 0x5);
+
+        // waiting for the key to go down again, then up again
+        emu.keypad.press(0x6);
+        emu.run_frame();
+        assert!((0x202..=0x204).contains(&emu.pc));
+        emu.keypad.press(0x5);
+        emu.run_frame();
+        assert!((0x206..=0x208).contains(&emu.pc));
+        emu.keypad.release(0x5);
+        emu.run_frame();
+        assert!(parked(&emu));
+        assert_eq!(emu.memory[0xE00], 0x5);
     }
 
     #[test]
@@ -0,0 +1,213 @@
+/*
+MEGA-CHIP extends SUPER-CHIP with a 256x192 screen where every pixel is an 8-bit index into a 256 colour palette. 0011
+switches it on and 0010 back to plain SUPER-CHIP. While it's on DXYN draws sprites of a configurable size (03NN width,
+04NN height) with one palette index per byte, index 0 being transparent, and blends them onto the screen with the mode
+picked by 080N. 02NN loads NN ARGB palette entries starting at index 1 and 05NN fades the whole screen.
+
+Besides the colours the screen also remembers the palette index of every pixel: DXYN sets VF when it draws over a pixel
+whose index is the collision colour picked by 09NN.
+*/
+
+pub const MEGA_WIDTH: usize = 256;
+pub const MEGA_HEIGHT: usize = 192;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    Normal,
+    Alpha25,  // 25% sprite, 75% screen
+    Alpha50,
+    Alpha75,  // 75% sprite, 25% screen
+    Additive,
+    Multiply,
+}
+
+impl BlendMode {
+    pub fn from_nibble(n: u8) -> BlendMode {
+        match n {
+            1 => BlendMode::Alpha25,
+            2 => BlendMode::Alpha50,
+            3 => BlendMode::Alpha75,
+            4 => BlendMode::Additive,
+            5 => BlendMode::Multiply,
+            _ => BlendMode::Normal,
+        }
+    }
+
+    fn blend(self, screen: u8, sprite: u8) -> u8 {
+        let (screen, sprite) = (screen as u16, sprite as u16);
+        let blended = match self {
+            BlendMode::Normal => sprite,
+            BlendMode::Alpha25 => (screen * 3 + sprite) / 4,
+            BlendMode::Alpha50 => (screen + sprite) / 2,
+            BlendMode::Alpha75 => (screen + sprite * 3) / 4,
+            BlendMode::Additive => (screen + sprite).min(0xFF),
+            BlendMode::Multiply => screen * sprite / 0xFF,
+        };
+        blended as u8
+    }
+}
+
+pub struct MegaChip {
+    pub enabled: bool,
+    palette: [[u8; 4]; 256],        // RGBA
+    pub sprite_width: usize,
+    pub sprite_height: usize,
+    pub alpha: u8,                  // screen fade, 0xFF is fully visible
+    pub blend_mode: BlendMode,
+    pub collision_colour: u8,
+    rgba: Vec<[u8; 4]>,             // MEGA_WIDTH * MEGA_HEIGHT pixels
+    indices: Vec<u8>,               // palette index last drawn to each pixel
+}
+
+impl MegaChip {
+    pub fn new() -> Self {
+        let mut palette = [[0x00, 0x00, 0x00, 0xff]; 256];
+        palette[1..].fill([0xff, 0xff, 0xff, 0xff]);
+
+        Self {
+            enabled: false,
+            palette,
+            sprite_width: 0,
+            sprite_height: 0,
+            alpha: 0xFF,
+            blend_mode: BlendMode::Normal,
+            collision_colour: 0,
+            rgba: vec![[0x00, 0x00, 0x00, 0xff]; MEGA_WIDTH * MEGA_HEIGHT],
+            indices: vec![0; MEGA_WIDTH * MEGA_HEIGHT],
+        }
+    }
+
+    /// Switch MEGA-CHIP mode on or off, both clear the screen
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.clear();
+    }
+
+    pub fn clear(&mut self) {
+        self.rgba.fill([0x00, 0x00, 0x00, 0xff]);
+        self.indices.fill(0);
+    }
+
+    /// 02NN, `colours` holds ARGB entries for palette index 1 onwards
+    pub fn load_palette(&mut self, colours: &[u8]) {
+        for (entry, argb) in self.palette[1..].iter_mut().zip(colours.chunks_exact(4)) {
+            *entry = [argb[1], argb[2], argb[3], argb[0]];
+        }
+    }
+
+    /// 03NN / 04NN, a size of 0 means 256
+    pub fn sprite_size(nn: u8) -> usize {
+        if nn == 0 { 256 } else { nn as usize }
+    }
+
+    /// Draw a sprite of `sprite_width` by `sprite_height` palette indices, clipping at the screen edges. Returns true if
+    /// a pixel was drawn over the collision colour.
+    pub fn draw_sprite(&mut self, x: usize, y: usize, sprite: &[u8]) -> bool {
+        let mut collision = false;
+        if self.sprite_width == 0 { // no size has been set yet
+            return collision;
+        }
+
+        for (row, indices) in sprite.chunks(self.sprite_width).take(self.sprite_height).enumerate() {
+            for (column, &index) in indices.iter().enumerate() {
+                let (px, py) = (x + column, y + row);
+                if index == 0 || px >= MEGA_WIDTH || py >= MEGA_HEIGHT {
+                    continue;
+                }
+
+                let pixel = py * MEGA_WIDTH + px;
+                if self.indices[pixel] != 0 && self.indices[pixel] == self.collision_colour {
+                    collision = true;
+                }
+                self.indices[pixel] = index;
+
+                let colour = self.palette[index as usize];
+                let screen = &mut self.rgba[pixel];
+                for (s, &c) in screen.iter_mut().zip(&colour).take(3) {
+                    *s = self.blend_mode.blend(*s, c);
+                }
+            }
+        }
+
+        collision
+    }
+
+    /// Scroll the screen by a signed amount of pixels, pixels scrolled in are blank
+    pub fn scroll(&mut self, dx: isize, dy: isize) {
+        let (old_rgba, old_indices) = (self.rgba.clone(), self.indices.clone());
+        for y in 0..MEGA_HEIGHT {
+            for x in 0..MEGA_WIDTH {
+                let (sx, sy) = (x as isize - dx, y as isize - dy);
+                let pixel = y * MEGA_WIDTH + x;
+                if (0..MEGA_WIDTH as isize).contains(&sx) && (0..MEGA_HEIGHT as isize).contains(&sy) {
+                    let source = sy as usize * MEGA_WIDTH + sx as usize;
+                    self.rgba[pixel] = old_rgba[source];
+                    self.indices[pixel] = old_indices[source];
+                } else {
+                    self.rgba[pixel] = [0x00, 0x00, 0x00, 0xff];
+                    self.indices[pixel] = 0;
+                }
+            }
+        }
+    }
+
+    /// Colour of a pixel with the screen fade applied
+    pub fn rgba(&self, pixel: usize) -> [u8; 4] {
+        let [r, g, b, _] = self.rgba[pixel];
+        let fade = |channel: u8| (channel as u16 * self.alpha as u16 / 0xFF) as u8;
+        [fade(r), fade(g), fade(b), 0xff]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sprites_use_the_palette_and_collide_with_the_collision_colour() {
+        let mut mega = MegaChip::new();
+        mega.load_palette(&[0xFF, 0x10, 0x20, 0x30, 0xFF, 0x40, 0x50, 0x60]);
+        mega.sprite_width = 2;
+        mega.sprite_height = 1;
+        mega.collision_colour = 2;
+
+        assert!(!mega.draw_sprite(0, 0, &[1, 2]));
+        assert_eq!(mega.rgba(0), [0x10, 0x20, 0x30, 0xff]);
+        assert_eq!(mega.rgba(1), [0x40, 0x50, 0x60, 0xff]);
+
+        // pixel 0 holds colour 1 which doesn't collide, pixel 1 holds the collision colour
+        assert!(!mega.draw_sprite(0, 0, &[1, 0]));
+        assert!(mega.draw_sprite(1, 0, &[1, 0]));
+    }
+
+    #[test]
+    fn blend_modes_follow_the_080n_nibble() {
+        // screen 0x40, sprite 0xC0
+        let blend = |n| BlendMode::from_nibble(n).blend(0x40, 0xC0);
+        assert_eq!(blend(0), 0xC0);
+        assert_eq!(blend(1), 0x60);
+        assert_eq!(blend(2), 0x80);
+        assert_eq!(blend(3), 0xA0);
+        assert_eq!(blend(4), 0xFF);
+        assert_eq!(blend(5), 0x30);
+        assert_eq!(BlendMode::from_nibble(6), BlendMode::Normal);
+    }
+
+    #[test]
+    fn blending_and_fading() {
+        let mut mega = MegaChip::new();
+        mega.load_palette(&[0xFF, 0xFF, 0x80, 0x00]);
+        mega.sprite_width = 1;
+        mega.sprite_height = 1;
+        mega.blend_mode = BlendMode::Alpha50;
+        mega.draw_sprite(5, 5, &[1]);
+        assert_eq!(mega.rgba(5 * MEGA_WIDTH + 5), [0x7F, 0x40, 0x00, 0xff]);
+
+        mega.alpha = 0;
+        assert_eq!(mega.rgba(5 * MEGA_WIDTH + 5), [0x00, 0x00, 0x00, 0xff]);
+
+        mega.alpha = 0xFF;
+        mega.scroll(-5, -5);
+        assert_eq!(mega.rgba(0), [0x7F, 0x40, 0x00, 0xff]);
+    }
+}
@@ -0,0 +1,37 @@
+/*
+Platforms, quirk presets, timings and fault policies are all picked by name on the command line, and all of them take the
+same spellings: case doesn't matter and dashes, underscores, spaces and dots are ignored, so "XO-CHIP", "xo_chip" and
+"xochip" name the same thing.
+*/
+
+use std::fmt;
+
+/// `name` lower cased with the separators dropped, ready to match against
+pub fn normalise(name: &str) -> String {
+    name.to_ascii_lowercase().replace(['-', '_', ' ', '.'], "")
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownName {
+    pub kind: &'static str,     // what was being named, e.g. "platform"
+    pub name: String,           // the name as it was given
+    pub expected: &'static str, // the names that would have worked
+}
+
+impl UnknownName {
+    pub fn new(kind: &'static str, name: &str, expected: &'static str) -> Self {
+        Self {
+            kind,
+            name: name.to_string(),
+            expected,
+        }
+    }
+}
+
+impl fmt::Display for UnknownName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown {} '{}', expected one of: {}", self.kind, self.name, self.expected)
+    }
+}
+
+impl std::error::Error for UnknownName {}
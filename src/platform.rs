@@ -0,0 +1,86 @@
+/*
+The platform decides what the machine looks like (how much memory there is, which instructions exist), the quirks decide
+how the shared instructions behave. Every platform comes with the quirks of its reference interpreter, which can still be
+overridden from the command line.
+*/
+
+use crate::quirks::Quirks;
+use crate::name::{normalise, UnknownName};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Platform {
+    Chip8,     // COSMAC VIP CHIP-8
+    Chip8X,    // COSMAC VIP CHIP-8X, with the colour and sound boards and a second keypad
+    Chip8E,    // COSMAC VIP CHIP-8E, extra skips, relative jumps and I/O
+    Chip10,    // COSMAC VIP CHIP-10, plain CHIP-8 on a 128x64 screen
+    #[default]
+    SuperChip, // SUPER-CHIP 1.1
+    XoChip,    // Octo's XO-CHIP
+    MegaChip,  // MEGA-CHIP, SUPER-CHIP with a 256x192 colour mode
+}
+
+impl Platform {
+    pub fn memory_size(self) -> usize {
+        match self {
+            Platform::XoChip => 0x10000,     // 64 KB
+            Platform::MegaChip => 0x1000000, // 16 MB, all of the 24-bit index register
+            _ => 0x1000,                 // 4 KB
+        }
+    }
+
+    /// CHIP-10 has no low resolution mode
+    pub fn fixed_hires(self) -> bool {
+        self == Platform::Chip10
+    }
+
+    /// Where programs are loaded and start running
+    pub fn program_start(self) -> u16 {
+        match self {
+            Platform::Chip8X => 0x300, // the CHIP-8X interpreter doesn't fit below 0x200
+            _ => 0x200,
+        }
+    }
+
+    /// Return addresses the interpreter had room for
+    pub fn stack_depth(self) -> usize {
+        match self {
+            Platform::Chip8 | Platform::Chip8X | Platform::Chip8E | Platform::Chip10 => 12, // the VIP's 24 bytes
+            Platform::SuperChip | Platform::XoChip | Platform::MegaChip => 16,
+        }
+    }
+
+    /// RPL user flags FX75/FX85 can save and restore
+    pub fn rpl_flags(self) -> usize {
+        match self {
+            Platform::SuperChip | Platform::MegaChip => 8, // what the HP48 had
+            Platform::XoChip => 16,
+            _ => 0,
+        }
+    }
+
+    pub fn quirks(self) -> Quirks {
+        match self {
+            Platform::SuperChip | Platform::MegaChip => Quirks::SUPER_CHIP,
+            Platform::XoChip => Quirks::XO_CHIP,
+            _ => Quirks::COSMAC_VIP,
+        }
+    }
+}
+
+impl FromStr for Platform {
+    type Err = UnknownName;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match normalise(s).as_str() {
+            "chip8" | "vip" => Ok(Platform::Chip8),
+            "chip8x" => Ok(Platform::Chip8X),
+            "chip8e" => Ok(Platform::Chip8E),
+            "chip10" => Ok(Platform::Chip10),
+            "schip" | "superchip" => Ok(Platform::SuperChip),
+            "xochip" => Ok(Platform::XoChip),
+            "megachip" | "mchip" => Ok(Platform::MegaChip),
+            _ => Err(UnknownName::new("platform", s, "chip8, chip8x, chip8e, chip10, schip, xochip, megachip")),
+        }
+    }
+}
@@ -0,0 +1,95 @@
+/*
+The CHIP8 family of interpreters disagree on a handful of instructions. Rather than picking one behaviour at compile
+time, the emulator carries a `Quirks` value that the executor consults. The presets follow the behaviour documented
+for each original interpreter, pick the one the ROM was written for.
+*/
+
+use crate::name::{normalise, UnknownName};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    pub shift_uses_vx: bool,     // 8XY6/8XYE shift VX in place instead of copying VY into VX first
+    pub jump_uses_vx: bool,      // BNNN jumps to NNN + VX (BXNN) instead of NNN + V0
+    pub load_store_inc: bool,    // FX55/FX65 leave the index register incremented past the last register
+    pub vf_reset: bool,          // 8XY1/8XY2/8XY3 reset VF to 0
+    pub clip_sprites: bool,      // DXYN clips sprites at the screen edge instead of wrapping them around
+    pub display_wait: bool,      // DXYN waits for the vertical blank before drawing
+    pub half_pixel_scroll: bool, // lores pixels are 2x2 hires pixels, so 00CN/00FB/00FC scroll half as far in lores
+}
+
+impl Quirks {
+    pub const COSMAC_VIP: Quirks = Quirks {
+        shift_uses_vx: false,
+        jump_uses_vx: false,
+        load_store_inc: true,
+        vf_reset: true,
+        clip_sprites: true,
+        display_wait: true,
+        half_pixel_scroll: false,
+    };
+
+    pub const CHIP_48: Quirks = Quirks {
+        shift_uses_vx: true,
+        jump_uses_vx: true,
+        load_store_inc: false,
+        vf_reset: false,
+        clip_sprites: true,
+        display_wait: false,
+        half_pixel_scroll: false,
+    };
+
+    pub const SUPER_CHIP: Quirks = Quirks {
+        shift_uses_vx: true,
+        jump_uses_vx: true,
+        load_store_inc: false,
+        vf_reset: false,
+        clip_sprites: true,
+        display_wait: false,
+        half_pixel_scroll: true,
+    };
+
+    pub const XO_CHIP: Quirks = Quirks {
+        shift_uses_vx: false,
+        jump_uses_vx: false,
+        load_store_inc: true,
+        vf_reset: false,
+        clip_sprites: false,
+        display_wait: false,
+        half_pixel_scroll: false,
+    };
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks::SUPER_CHIP
+    }
+}
+
+impl FromStr for Quirks {
+    type Err = UnknownName;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match normalise(s).as_str() {
+            "vip" | "cosmacvip" | "chip8" => Ok(Quirks::COSMAC_VIP),
+            "chip48" => Ok(Quirks::CHIP_48),
+            "schip" | "superchip" | "schip11" | "superchip11" => Ok(Quirks::SUPER_CHIP),
+            "xochip" => Ok(Quirks::XO_CHIP),
+            _ => Err(UnknownName::new("quirk preset", s, "vip, chip48, schip, xochip")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_preset_names() {
+        assert_eq!("vip".parse(), Ok(Quirks::COSMAC_VIP));
+        assert_eq!("SUPER-CHIP 1.1".parse(), Ok(Quirks::SUPER_CHIP));
+        assert_eq!("X0-CHIP".parse::<Quirks>().unwrap_err().name, "X0-CHIP");
+        assert_eq!("XO-CHIP".parse(), Ok(Quirks::XO_CHIP));
+        assert_eq!("Chip_48".parse(), Ok(Quirks::CHIP_48));
+    }
+}
@@ -0,0 +1,127 @@
+/*
+SUPER-CHIP borrowed the HP48's RPL user flags as a place to keep 8 bytes (XO-CHIP: 16) between runs, usually high scores.
+FX75 saves V0 -> VX into the flags and FX85 restores them, registers past the last flag are left alone. To make them
+survive restarts the flags are written to <config dir>/chip8/rpl/<rom hash> whenever FX75 changes them, the hash being
+FNV-1a over the ROM bytes so it stays stable across builds. Games tend to run FX75 every frame, so unchanged flags aren't
+written again.
+*/
+
+use crate::config::config_dir;
+use log::warn;
+use std::{fs, path::PathBuf};
+
+pub struct RplFlags {
+    flags: Vec<u8>,        // as many as the platform has
+    path: Option<PathBuf>, // where the flags are persisted, None keeps them in memory only
+}
+
+impl RplFlags {
+    /// `count` flags that only live as long as the emulator
+    pub fn new(count: usize) -> Self {
+        Self {
+            flags: vec![0; count],
+            path: None,
+        }
+    }
+
+    /// `count` flags persisted for this ROM, restored from disk if they were saved before
+    pub fn for_rom(rom: &[u8], count: usize) -> Self {
+        let Some(dir) = config_dir() else {
+            warn!("no config directory found, RPL flags won't be saved");
+            return Self::new(count);
+        };
+
+        Self::at(dir.join("chip8").join("rpl").join(format!("{:016x}", rom_hash(rom))), count)
+    }
+
+    fn at(path: PathBuf, count: usize) -> Self {
+        let mut flags = vec![0; count];
+        if let Ok(saved) = fs::read(&path) {
+            let len = saved.len().min(count);
+            flags[..len].copy_from_slice(&saved[..len]);
+        }
+
+        Self {
+            flags,
+            path: Some(path),
+        }
+    }
+
+    pub fn load(&self, registers: &mut [u8]) {
+        let len = self.clamp(registers.len());
+        registers[..len].copy_from_slice(&self.flags[..len]);
+    }
+
+    pub fn save(&mut self, registers: &[u8]) {
+        let len = self.clamp(registers.len());
+        if self.flags[..len] == registers[..len] {
+            return;
+        }
+        self.flags[..len].copy_from_slice(&registers[..len]);
+
+        if let Some(path) = &self.path {
+            let result = path
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| fs::write(path, &self.flags));
+            if let Err(err) = result {
+                warn!("couldn't save RPL flags to {}: {err}", path.display());
+            }
+        }
+    }
+
+    // registers FX75/FX85 can reach out of `len`
+    fn clamp(&self, len: usize) -> usize {
+        if len > self.flags.len() {
+            warn!("only {} RPL flags, V{:X} and up are left alone", self.flags.len(), self.flags.len());
+        }
+        len.min(self.flags.len())
+    }
+}
+
+pub fn rom_hash(rom: &[u8]) -> u64 { // 64-bit FNV-1a
+    rom.iter().fold(0xcbf29ce484222325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fnv1a_hash() {
+        assert_eq!(rom_hash(b""), 0xcbf29ce484222325);
+        assert_eq!(rom_hash(b"a"), 0xaf63dc4c8601ec8c);
+    }
+
+    #[test]
+    fn flags_survive_a_restart() {
+        let path = std::env::temp_dir().join(format!("chip8-rpl-test-{}", std::process::id()));
+
+        let mut flags = RplFlags::at(path.clone(), 8);
+        flags.save(&[1, 2, 3]);
+
+        let mut registers = [0; 4];
+        RplFlags::at(path.clone(), 8).load(&mut registers);
+        assert_eq!(registers, [1, 2, 3, 0]);
+
+        // saving the same flags again doesn't touch the file
+        fs::remove_file(&path).unwrap();
+        flags.save(&[1, 2]);
+        assert!(!path.exists());
+        flags.save(&[1, 2, 4]);
+        assert!(path.exists());
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn registers_past_the_last_flag_are_left_alone() {
+        let mut flags = RplFlags::new(8);
+        flags.save(&[0xAA; 16]);
+
+        let mut registers = [0x55; 16];
+        flags.load(&mut registers);
+        assert_eq!(registers[..8], [0xAA; 8]);
+        assert_eq!(registers[8..], [0x55; 8]);
+    }
+}
@@ -0,0 +1,210 @@
+/*
+The interpreters CHIP8 programs were written for ran a roughly fixed number of instructions between two 60 Hz display
+interrupts, and the interrupt is what counted the delay and sound timers down. The scheduler keeps that shape: it works
+out how many 60 Hz frames have passed since it last looked, and each frame runs a budget of instructions followed by
+exactly one timer tick.
+
+Time comes from a `Clock` so the frontend can use the system clock while tests step a `ManualClock` by hand. The scheduler
+also keeps frame pacing statistics: how many frames ran, how many were dropped after stalls and how late frames ran
+compared to when they were due, plus the VIP machine cycles each frame used.
+*/
+
+use crate::timing::Timing;
+use std::{cell::Cell, rc::Rc, time::{Duration, Instant}};
+
+pub const FRAME_RATE: u32 = 60;
+pub const FRAME: Duration = Duration::from_nanos(1_000_000_000 / FRAME_RATE as u64);
+const MAX_CATCH_UP_FRAMES: u32 = 4; // frames run at most per update, the rest are dropped after a stall
+
+pub trait Clock {
+    /// Time since some fixed point, only the difference between two calls matters
+    fn now(&self) -> Duration;
+}
+
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// A clock that only moves when it's told to, clones share the same time
+#[derive(Clone, Default)]
+pub struct ManualClock {
+    now: Rc<Cell<Duration>>,
+}
+
+impl ManualClock {
+    pub fn advance(&self, duration: Duration) {
+        self.now.set(self.now.get() + duration);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        self.now.get()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    pub frames: u64,             // frames run
+    pub dropped: u64,            // frames skipped to catch up after a stall
+    pub worst_lateness: Duration,
+    total_lateness: Duration,    // summed over every update that ran frames
+    updates: u64,
+    pub cycles_per_frame: u32,   // VIP machine cycles the last frame ran
+    total_cycles: u64,
+}
+
+impl FrameStats {
+    /// How long after it was due the last frame of an update ran, on average
+    pub fn average_lateness(&self) -> Duration {
+        if self.updates == 0 {
+            Duration::ZERO
+        } else {
+            self.total_lateness / self.updates as u32
+        }
+    }
+
+    /// VIP machine cycles per frame, on average
+    pub fn average_cycles_per_frame(&self) -> u32 {
+        self.total_cycles.checked_div(self.frames).unwrap_or(0) as u32
+    }
+
+    fn record(&mut self, frames: u32, dropped: u32, lateness: Duration) {
+        self.frames += frames as u64;
+        self.dropped += dropped as u64;
+        self.worst_lateness = self.worst_lateness.max(lateness);
+        self.total_lateness += lateness;
+        self.updates += 1;
+    }
+}
+
+pub struct Scheduler {
+    clock: Box<dyn Clock>,
+    pub instructions_per_frame: usize, // budget with `Timing::Instructions`
+    pub timing: Timing,
+    last_frame: Duration, // when the last frame that ran was due
+    stats: FrameStats,
+}
+
+impl Scheduler {
+    pub fn new(clock: Box<dyn Clock>, instructions_per_frame: usize) -> Self {
+        let last_frame = clock.now();
+        Self {
+            clock,
+            instructions_per_frame,
+            timing: Timing::default(),
+            last_frame,
+            stats: FrameStats::default(),
+        }
+    }
+
+    /// Instructions per frame closest to running `instructions_per_second`
+    pub fn budget_for(instructions_per_second: usize) -> usize {
+        (instructions_per_second + FRAME_RATE as usize / 2) / FRAME_RATE as usize
+    }
+
+    /// Frames that have come due since the last call
+    pub fn frames_due(&mut self) -> u32 {
+        let now = self.clock.now();
+        let elapsed = now.saturating_sub(self.last_frame);
+        let frames = (elapsed.as_nanos() / FRAME.as_nanos()) as u32;
+        if frames == 0 {
+            return 0;
+        }
+
+        if frames > MAX_CATCH_UP_FRAMES {
+            self.stats.record(MAX_CATCH_UP_FRAMES, frames - MAX_CATCH_UP_FRAMES, Duration::ZERO);
+            self.last_frame = now;
+            return MAX_CATCH_UP_FRAMES;
+        }
+
+        self.last_frame += FRAME * frames;
+        self.stats.record(frames, 0, now - self.last_frame);
+        frames
+    }
+
+    /// Time until the next frame is due
+    pub fn until_next_frame(&self) -> Duration {
+        (self.last_frame + FRAME).saturating_sub(self.clock.now())
+    }
+
+    /// Record the machine cycles a frame ran
+    pub fn record_cycles(&mut self, cycles: u32) {
+        self.stats.cycles_per_frame = cycles;
+        self.stats.total_cycles += cycles as u64;
+    }
+
+    pub fn stats(&self) -> FrameStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_come_due_every_sixtieth_of_a_second() {
+        let clock = ManualClock::default();
+        let mut scheduler = Scheduler::new(Box::new(clock.clone()), 10);
+        assert_eq!(scheduler.frames_due(), 0);
+
+        clock.advance(FRAME / 2);
+        assert_eq!(scheduler.frames_due(), 0);
+        clock.advance(FRAME / 2);
+        assert_eq!(scheduler.frames_due(), 1);
+        assert_eq!(scheduler.frames_due(), 0);
+
+        // the remainder carries over to the next frame
+        clock.advance(FRAME * 2 + FRAME / 2);
+        assert_eq!(scheduler.frames_due(), 2);
+        clock.advance(FRAME / 2);
+        assert_eq!(scheduler.frames_due(), 1);
+    }
+
+    #[test]
+    fn stalls_drop_frames() {
+        let clock = ManualClock::default();
+        let mut scheduler = Scheduler::new(Box::new(clock.clone()), 10);
+        clock.advance(FRAME * 10);
+        assert_eq!(scheduler.frames_due(), MAX_CATCH_UP_FRAMES);
+        assert_eq!(scheduler.frames_due(), 0);
+        assert_eq!(scheduler.stats().dropped, 10 - MAX_CATCH_UP_FRAMES as u64);
+    }
+
+    #[test]
+    fn pacing_stats_track_late_frames() {
+        let clock = ManualClock::default();
+        let mut scheduler = Scheduler::new(Box::new(clock.clone()), 10);
+        clock.advance(FRAME + Duration::from_millis(2));
+        scheduler.frames_due();
+        assert_eq!(scheduler.until_next_frame(), FRAME - Duration::from_millis(2));
+        clock.advance(FRAME - Duration::from_millis(2));
+        scheduler.frames_due();
+
+        let stats = scheduler.stats();
+        assert_eq!(stats.frames, 2);
+        assert_eq!(stats.worst_lateness, Duration::from_millis(2));
+        assert_eq!(stats.average_lateness(), Duration::from_millis(1));
+    }
+
+    #[test]
+    fn budget_rounds_to_the_nearest_instruction() {
+        assert_eq!(Scheduler::budget_for(700), 12);
+        assert_eq!(Scheduler::budget_for(600), 10);
+    }
+}
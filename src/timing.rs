@@ -0,0 +1,101 @@
+/*
+How much work fits in a 60 Hz frame. The default runs a flat budget of instructions per frame, which is what most modern
+interpreters do. `Timing::CosmacVip` instead charges every instruction what it cost on the original VIP, so programs
+written for it run at the speed they did in 1977.
+
+The VIP's 1802 ran at 1.7609 MHz and took 8 clock cycles per machine cycle, which is 3668 machine cycles per frame. The
+display DMA steals 1024 of them (128 lines of 8 bytes) and the interrupt routine that counts the timers down another 46,
+leaving the interpreter 2598. The costs below are machine cycles for each instruction's routine in the VIP interpreter,
+on top of the 18 cycles of fetching and dispatching it. They follow Laurence Scotford's annotated disassembly of the
+interpreter ("Chip-8 on the COSMAC VIP", laurencescotford.net), where most 1802 instructions take 2 machine cycles and
+long branches 3, and are rounded where the routine loops a data dependent number of times. Clearing the screen is by far
+the most expensive thing a program can do: the routine zeroes 256 bytes of display memory at 12 cycles a byte. Sprites
+are the other big variable: every row is shifted into place bit by bit, so a sprite that isn't byte aligned costs more.
+*/
+
+use crate::instruction::Instruction;
+use crate::name::{normalise, UnknownName};
+use std::str::FromStr;
+
+pub const VIP_CYCLES_PER_FRAME: u32 = 3668;
+const VIP_INTERRUPT_CYCLES: u32 = 1024 + 46; // display DMA and the timer interrupt
+pub const VIP_INTERPRETER_CYCLES: u32 = VIP_CYCLES_PER_FRAME - VIP_INTERRUPT_CYCLES;
+pub const VIP_FETCH_CYCLES: u32 = 18;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Timing {
+    #[default]
+    Instructions, // a fixed number of instructions per frame
+    CosmacVip,    // instructions cost the machine cycles they took on the VIP
+}
+
+/// Machine cycles the VIP interpreter spent on `instruction`, with the registers as they are before it runs
+pub fn vip_cycles(instruction: &Instruction, registers: &[u8; 16]) -> u32 {
+    use Instruction::*;
+
+    let execute = match *instruction {
+        Clear => 3078, // 256 bytes of display at 12 cycles each, plus setting up the pointer
+        Return | Jump { .. } | JumpOffset { .. } | Call { .. } => 23,
+        SkipEqImm { .. } | SkipNeImm { .. } | LoadIndex { .. } => 12,
+        SkipEqReg { .. } | SkipNeReg { .. } | SkipKey { .. } | SkipNotKey { .. } | WaitKey { .. } => 16,
+        LoadImm { .. } => 6,
+        AddImm { .. } | LoadDelay { .. } | SetDelay { .. } | SetSound { .. } => 10,
+        Move { .. } | Or { .. } | And { .. } | Xor { .. } | Add { .. } | Sub { .. } | ShiftRight { .. }
+        | SubReverse { .. } | ShiftLeft { .. } => 44, // built as a small 1802 routine on the stack and run
+        Random { .. } => 36,
+        AddIndex { .. } => 19,
+        LoadFont { .. } => 20,
+        StoreBcd { .. } => 204,
+        StoreRegisters { x } | LoadRegisters { x } => 14 + 14 * (x as u32 + 1),
+        Draw { x, n, .. } => {
+            let rows = if n == 0 { 16 } else { n as u32 };
+            let shift = registers[x as usize] as u32 % 8; // bits each row byte is shifted right by
+            50 + rows * (16 + 4 * shift)
+        },
+        _ => 0, // not part of the VIP interpreter
+    };
+
+    VIP_FETCH_CYCLES + execute
+}
+
+impl FromStr for Timing {
+    type Err = UnknownName;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match normalise(s).as_str() {
+            "instructions" | "fixed" => Ok(Timing::Instructions),
+            "vip" | "cosmacvip" => Ok(Timing::CosmacVip),
+            _ => Err(UnknownName::new("timing", s, "instructions, vip")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unaligned_sprites_cost_more() {
+        let mut registers = [0; 16];
+        let draw = Instruction::Draw { x: 0, y: 1, n: 5 };
+        let aligned = vip_cycles(&draw, &registers);
+        registers[0] = 3;
+        assert!(vip_cycles(&draw, &registers) > aligned);
+        assert_eq!(aligned, VIP_FETCH_CYCLES + 50 + 5 * 16);
+    }
+
+    #[test]
+    fn clearing_outweighs_a_frame_of_display_dma() {
+        let registers = [0; 16];
+        assert!(vip_cycles(&Instruction::Clear, &registers) > 1024);
+        assert_eq!(vip_cycles(&Instruction::Add { x: 0, y: 1 }, &registers), VIP_FETCH_CYCLES + 44);
+        assert_eq!(vip_cycles(&Instruction::Random { x: 0, nn: 0xFF }, &registers), VIP_FETCH_CYCLES + 36);
+    }
+
+    #[test]
+    fn parses_timing_names() {
+        assert_eq!("VIP".parse(), Ok(Timing::CosmacVip));
+        assert_eq!("fixed".parse(), Ok(Timing::Instructions));
+        assert!("fast".parse::<Timing>().is_err());
+    }
+}